@@ -0,0 +1,129 @@
+use std::{
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use super::{Expr, Expression, IRType, Int, SquareType};
+
+/// Containers that can be indexed inside kernels
+pub trait Indexed: SquareType {
+    type Item: SquareType;
+}
+
+/// Runtime sized array, passed to kernels by reference as a storage buffer. Only accessible
+/// inside kernels.
+pub struct Array<T> {
+    _type: PhantomData<T>,
+}
+
+// Manual impls so handles to arrays are copyable regardless of `T`
+impl<T> Clone for Array<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Array<T> {}
+
+/// Arrays are typed by their elements, they're only used through indexing
+impl<T: SquareType> SquareType for Array<T> {
+    fn ir_type() -> IRType {
+        T::ir_type()
+    }
+}
+
+impl<T: SquareType> Indexed for Array<T> {
+    type Item = T;
+}
+
+impl<T, I: Int> Index<I> for Array<T> {
+    type Output = T;
+
+    fn index(&self, _index: I) -> &Self::Output {
+        unimplemented!("Arrays can only be indexed inside kernels")
+    }
+}
+
+impl<T, I: Int> IndexMut<I> for Array<T> {
+    fn index_mut(&mut self, _index: I) -> &mut Self::Output {
+        unimplemented!("Arrays can only be indexed inside kernels")
+    }
+}
+
+/// Fixed size array in workgroup shared memory, visible to all invocations of a workgroup. The
+/// size must be known at expansion time.
+pub struct SharedMemory<T> {
+    _type: PhantomData<T>,
+}
+
+impl<T> SharedMemory<T> {
+    pub fn new(_size: u32) -> Self {
+        unimplemented!("Shared memory can only be allocated inside kernels")
+    }
+}
+
+impl<T> Clone for SharedMemory<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SharedMemory<T> {}
+
+impl<T: SquareType> SquareType for SharedMemory<T> {
+    fn ir_type() -> IRType {
+        T::ir_type()
+    }
+}
+
+impl<T: SquareType> Indexed for SharedMemory<T> {
+    type Item = T;
+}
+
+impl<T, I: Int> Index<I> for SharedMemory<T> {
+    type Output = T;
+
+    fn index(&self, _index: I) -> &Self::Output {
+        unimplemented!("Shared memory can only be indexed inside kernels")
+    }
+}
+
+impl<T, I: Int> IndexMut<I> for SharedMemory<T> {
+    fn index_mut(&mut self, _index: I) -> &mut Self::Output {
+        unimplemented!("Shared memory can only be indexed inside kernels")
+    }
+}
+
+/// `SharedMemory::new`, only valid as the initializer of a local
+pub struct SharedMemoryExpr<T: SquareType> {
+    pub size: u32,
+    pub _type: PhantomData<T>,
+}
+
+impl<T: SquareType> Expr for SharedMemoryExpr<T> {
+    type Output = SharedMemory<T>;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::SharedMemory {
+            size: self.size,
+            ty: <T as SquareType>::ir_type(),
+        }
+    }
+}
+
+pub struct IndexExpr<A: Indexed, I: Int> {
+    pub array: Box<dyn Expr<Output = A>>,
+    pub index: Box<dyn Expr<Output = I>>,
+}
+
+impl<A: Indexed, I: Int> Expr for IndexExpr<A, I> {
+    type Output = A::Item;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Index {
+            array: Box::new(self.array.expression_untyped()),
+            index: Box::new(self.index.expression_untyped()),
+            ty: <A::Item as SquareType>::ir_type(),
+        }
+    }
+}
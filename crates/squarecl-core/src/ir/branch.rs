@@ -0,0 +1,110 @@
+use super::{Expr, Expression, Int, Literal, SquareType, Statement, Variable};
+
+pub struct Block<T: SquareType> {
+    pub statements: Vec<Statement>,
+    pub value: Option<Box<dyn Expr<Output = T>>>,
+}
+
+impl<T: SquareType> Expr for Block<T> {
+    type Output = T;
+
+    fn expression_untyped(&self) -> Expression {
+        let mut block = self.statements.clone();
+        if let Some(value) = &self.value {
+            block.push(Statement::ImplicitReturn {
+                expression: Box::new(value.expression_untyped()),
+            });
+        }
+        Expression::Block {
+            block,
+            ty: <T as SquareType>::ir_type(),
+        }
+    }
+}
+
+pub struct Branch {
+    pub condition: Box<dyn Expr<Output = bool>>,
+    pub then_block: Vec<Statement>,
+    pub else_block: Option<Vec<Statement>>,
+}
+
+impl Expr for Branch {
+    type Output = ();
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Branch {
+            condition: Box::new(self.condition.expression_untyped()),
+            then_block: self.then_block.clone(),
+            else_block: self.else_block.clone(),
+        }
+    }
+}
+
+/// Integer switch. The bound rejects non-integer values the macro can't type.
+pub struct Switch<T: Int> {
+    pub value: Box<dyn Expr<Output = T>>,
+    pub cases: Vec<(Vec<Literal<T>>, Vec<Statement>)>,
+    pub default: Vec<Statement>,
+}
+
+impl<T: Int> Expr for Switch<T> {
+    type Output = ();
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Switch {
+            value: Box::new(self.value.expression_untyped()),
+            cases: self
+                .cases
+                .iter()
+                .map(|(values, block)| {
+                    let values = values
+                        .iter()
+                        .map(|value| value.expression_untyped())
+                        .collect();
+                    (values, block.clone())
+                })
+                .collect(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+pub struct ForLoop<T: SquareType> {
+    pub label: Option<String>,
+    pub variable: Variable<T>,
+    pub start: Box<dyn Expr<Output = T>>,
+    pub end: Box<dyn Expr<Output = T>>,
+    pub step: Option<Box<dyn Expr<Output = T>>>,
+    pub inclusive: bool,
+    pub block: Vec<Statement>,
+}
+
+impl<T: SquareType> From<ForLoop<T>> for Statement {
+    fn from(value: ForLoop<T>) -> Self {
+        Statement::ForLoop {
+            label: value.label,
+            variable: Box::new(value.variable.expression_untyped()),
+            start: Box::new(value.start.expression_untyped()),
+            end: Box::new(value.end.expression_untyped()),
+            step: value.step.map(|step| Box::new(step.expression_untyped())),
+            inclusive: value.inclusive,
+            block: value.block,
+        }
+    }
+}
+
+pub struct WhileLoop {
+    pub label: Option<String>,
+    pub condition: Box<dyn Expr<Output = bool>>,
+    pub block: Vec<Statement>,
+}
+
+impl From<WhileLoop> for Statement {
+    fn from(value: WhileLoop) -> Self {
+        Statement::WhileLoop {
+            label: value.label,
+            condition: Box::new(value.condition.expression_untyped()),
+            block: value.block,
+        }
+    }
+}
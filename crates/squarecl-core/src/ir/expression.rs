@@ -1,11 +1,16 @@
 use std::{
     fmt::Display,
     marker::PhantomData,
-    ops::{Add, Deref, Div, Mul, Neg, Not, Sub},
+    ops::{
+        Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Deref, Div,
+        DivAssign, Mul, MulAssign, Neg, Not, Rem, RemAssign, Shl, ShlAssign, Shr, ShrAssign, Sub,
+        SubAssign,
+    },
 };
 
-use super::{operator::Operator, IRType, SquareType};
+use super::{operator::Operator, Function, IRType, Intrinsic, SquareType, Statement};
 
+#[derive(Clone, Debug)]
 pub enum Expression {
     Binary {
         left: Box<Expression>,
@@ -32,12 +37,79 @@ pub enum Expression {
         right: Box<Expression>,
         ty: IRType,
     },
+    /// Compound assignment like `left += right`. `left` is only evaluated once.
+    CompoundAssignment {
+        left: Box<Expression>,
+        operator: Operator,
+        right: Box<Expression>,
+        ty: IRType,
+    },
+    /// Primitive `as` cast. Float to integer casts follow Rust and saturate at the bounds of the
+    /// destination type, with `NaN` mapping to `0`.
+    Cast {
+        input: Box<Expression>,
+        from: IRType,
+        to: IRType,
+    },
+    /// Built-in math function
+    Intrinsic {
+        intrinsic: Intrinsic,
+        args: Vec<Expression>,
+        ty: IRType,
+    },
+    /// Call to a separately emitted function
+    Call {
+        function: Box<Function>,
+        args: Vec<Expression>,
+        ty: IRType,
+    },
+    /// Array element, usable as an assignment target
+    Index {
+        array: Box<Expression>,
+        index: Box<Expression>,
+        ty: IRType,
+    },
+    /// Field of a struct, usable as an assignment target
+    Field {
+        base: Box<Expression>,
+        name: String,
+        ty: IRType,
+    },
+    /// Tuple value, a struct of type `ty` with the elements as fields
+    Tuple {
+        elements: Vec<Expression>,
+        ty: IRType,
+    },
+    /// Allocation of `size` elements in workgroup shared memory. Only valid as the initializer of
+    /// a local, backends declare it in the workgroup address space.
+    SharedMemory {
+        size: u32,
+        ty: IRType,
+    },
     /// Local variable initializer
     Init {
         left: Box<Expression>,
         right: Box<Expression>,
         ty: IRType,
     },
+    /// Block with its own scope. If the block has a value, it's the trailing `ImplicitReturn`.
+    Block {
+        block: Vec<Statement>,
+        ty: IRType,
+    },
+    /// `if`/`else` branch. `else if` chains are lowered to an else block containing a single
+    /// nested branch.
+    Branch {
+        condition: Box<Expression>,
+        then_block: Vec<Statement>,
+        else_block: Option<Vec<Statement>>,
+    },
+    /// Integer `match`. Each case matches one or more literal values.
+    Switch {
+        value: Box<Expression>,
+        cases: Vec<(Vec<Expression>, Vec<Statement>)>,
+        default: Vec<Statement>,
+    },
 }
 
 impl Expression {
@@ -48,7 +120,18 @@ impl Expression {
             Expression::Variable { ty, .. } => *ty,
             Expression::Literal { ty, .. } => *ty,
             Expression::Assigment { ty, .. } => *ty,
+            Expression::CompoundAssignment { ty, .. } => *ty,
+            Expression::Cast { to, .. } => *to,
+            Expression::Intrinsic { ty, .. } => *ty,
+            Expression::Call { ty, .. } => *ty,
+            Expression::Index { ty, .. } => *ty,
+            Expression::Field { ty, .. } => *ty,
+            Expression::Tuple { ty, .. } => *ty,
+            Expression::SharedMemory { ty, .. } => *ty,
             Expression::Init { ty, .. } => *ty,
+            Expression::Block { ty, .. } => *ty,
+            Expression::Branch { .. } => IRType::Unit,
+            Expression::Switch { .. } => IRType::Unit,
         }
     }
 }
@@ -94,6 +177,77 @@ macro_rules! bin_op {
     };
 }
 
+/// Comparisons are bounded on the comparison trait of the operands and always produce a `bool`
+macro_rules! cmp_op {
+    ($name:ident, $trait:ident, $operator:path) => {
+        pub struct $name<TLeft, TRight>(pub BinaryOp<TLeft, TRight, bool>)
+        where
+            TLeft: $trait<TRight>;
+
+        impl<TLeft, TRight> Expr for $name<TLeft, TRight>
+        where
+            TLeft: $trait<TRight>,
+        {
+            type Output = bool;
+
+            fn expression_untyped(&self) -> Expression {
+                Expression::Binary {
+                    left: Box::new(self.0.left.expression_untyped()),
+                    right: Box::new(self.0.right.expression_untyped()),
+                    operator: $operator,
+                    ty: IRType::Bool,
+                }
+            }
+        }
+    };
+}
+
+/// Logical operators only take `bool` operands. The right operand stays a separate subtree, so
+/// backends can evaluate it lazily.
+macro_rules! logic_op {
+    ($name:ident, $operator:path) => {
+        pub struct $name(pub BinaryOp<bool, bool, bool>);
+
+        impl Expr for $name {
+            type Output = bool;
+
+            fn expression_untyped(&self) -> Expression {
+                Expression::Binary {
+                    left: Box::new(self.0.left.expression_untyped()),
+                    right: Box::new(self.0.right.expression_untyped()),
+                    operator: $operator,
+                    ty: IRType::Bool,
+                }
+            }
+        }
+    };
+}
+
+/// Compound assignments are bounded on the matching `*Assign` trait and produce `()`
+macro_rules! assign_op {
+    ($name:ident, $trait:ident, $operator:path) => {
+        pub struct $name<TLeft: SquareType, TRight>(pub BinaryOp<TLeft, TRight, ()>)
+        where
+            TLeft: $trait<TRight>;
+
+        impl<TLeft: SquareType, TRight> Expr for $name<TLeft, TRight>
+        where
+            TLeft: $trait<TRight>,
+        {
+            type Output = ();
+
+            fn expression_untyped(&self) -> Expression {
+                Expression::CompoundAssignment {
+                    left: Box::new(self.0.left.expression_untyped()),
+                    operator: $operator,
+                    right: Box::new(self.0.right.expression_untyped()),
+                    ty: <TLeft as SquareType>::ir_type(),
+                }
+            }
+        }
+    };
+}
+
 macro_rules! unary_op {
     ($name:ident, $trait:ident, $operator:path, $target:ident) => {
         pub struct $name<TIn: $trait<$target = TOut>, TOut>(pub UnaryOp<TIn, TOut>);
@@ -116,6 +270,33 @@ bin_op!(AddExpr, Add, Operator::Add);
 bin_op!(SubExpr, Sub, Operator::Sub);
 bin_op!(MulExpr, Mul, Operator::Mul);
 bin_op!(DivExpr, Div, Operator::Div);
+bin_op!(RemExpr, Rem, Operator::Rem);
+bin_op!(BitAndExpr, BitAnd, Operator::BitAnd);
+bin_op!(BitOrExpr, BitOr, Operator::BitOr);
+bin_op!(BitXorExpr, BitXor, Operator::BitXor);
+bin_op!(ShlExpr, Shl, Operator::Shl);
+bin_op!(ShrExpr, Shr, Operator::Shr);
+
+cmp_op!(EqExpr, PartialEq, Operator::Eq);
+cmp_op!(NeExpr, PartialEq, Operator::Ne);
+cmp_op!(LtExpr, PartialOrd, Operator::Lt);
+cmp_op!(LeExpr, PartialOrd, Operator::Le);
+cmp_op!(GtExpr, PartialOrd, Operator::Gt);
+cmp_op!(GeExpr, PartialOrd, Operator::Ge);
+
+logic_op!(AndExpr, Operator::And);
+logic_op!(OrExpr, Operator::Or);
+
+assign_op!(AddAssignExpr, AddAssign, Operator::Add);
+assign_op!(SubAssignExpr, SubAssign, Operator::Sub);
+assign_op!(MulAssignExpr, MulAssign, Operator::Mul);
+assign_op!(DivAssignExpr, DivAssign, Operator::Div);
+assign_op!(RemAssignExpr, RemAssign, Operator::Rem);
+assign_op!(BitAndAssignExpr, BitAndAssign, Operator::BitAnd);
+assign_op!(BitOrAssignExpr, BitOrAssign, Operator::BitOr);
+assign_op!(BitXorAssignExpr, BitXorAssign, Operator::BitXor);
+assign_op!(ShlAssignExpr, ShlAssign, Operator::Shl);
+assign_op!(ShrAssignExpr, ShrAssign, Operator::Shr);
 
 unary_op!(NotExpr, Not, Operator::Not, Output);
 unary_op!(NegExpr, Neg, Operator::Neg, Output);
@@ -186,3 +367,20 @@ impl<T: SquareType> Expr for Initializer<T> {
         }
     }
 }
+
+pub struct Cast<TFrom: SquareType, TTo: SquareType> {
+    pub input: Box<dyn Expr<Output = TFrom>>,
+    pub _to: PhantomData<TTo>,
+}
+
+impl<TFrom: SquareType, TTo: SquareType> Expr for Cast<TFrom, TTo> {
+    type Output = TTo;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Cast {
+            input: Box::new(self.input.expression_untyped()),
+            from: <TFrom as SquareType>::ir_type(),
+            to: <TTo as SquareType>::ir_type(),
+        }
+    }
+}
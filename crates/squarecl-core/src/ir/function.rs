@@ -0,0 +1,140 @@
+use std::marker::PhantomData;
+
+use super::{Expr, Expression, IRType, SquareType, Statement};
+
+/// Expanded `#[square]` function that can be called from other kernels
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    /// Concrete types of the generic parameters, chosen at expansion time
+    pub generics: Vec<(String, IRType)>,
+    pub params: Vec<(String, IRType)>,
+    /// Array parameters, bound as storage buffers in declaration order
+    pub bindings: Vec<Binding>,
+    pub return_type: IRType,
+    pub body: Vec<Statement>,
+    /// Inline the body at each call site instead of calling a separate function
+    pub inline: bool,
+    /// Workgroup size when dispatched as a compute shader
+    pub workgroup_size: [u32; 3],
+    /// Name of the entry point when dispatched, `name` if not set
+    pub entry: Option<String>,
+    /// Guard array accesses against out of bounds indices. Turned off by `launch_unchecked`.
+    pub bounds_checks: bool,
+}
+
+impl Function {
+    pub fn entry_name(&self) -> &str {
+        self.entry.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Name of a specialization, built from the function name, generic types and comptime
+    /// values. ASCII alphanumerics are kept, any other character is written as `_{hex}_` and
+    /// parts are joined with `__`, so distinct specializations never share a name.
+    pub fn mangle(parts: impl IntoIterator<Item = String>) -> String {
+        let escape = |part: String| {
+            part.chars()
+                .map(|c| match c.is_ascii_alphanumeric() {
+                    true => c.to_string(),
+                    false => format!("_{:x}_", c as u32),
+                })
+                .collect::<String>()
+        };
+        parts.into_iter().map(escape).collect::<Vec<_>>().join("__")
+    }
+
+    /// Lower a call to a block that binds the arguments to the parameters, followed by the body.
+    /// Arguments are bound to temporaries first when there are multiple parameters, so an
+    /// argument can't see a parameter shadowing one of the caller's variables.
+    fn inline_call(&self, args: Vec<Expression>) -> Expression {
+        let bind = |name: String, ty: IRType, value: Expression| Statement::Local {
+            variable: Box::new(Expression::Init {
+                left: Box::new(Expression::Variable { name, ty }),
+                right: Box::new(value),
+                ty,
+            }),
+            mutable: false,
+            ty: Some(ty),
+        };
+
+        let mut block = Vec::new();
+        if let [(name, ty)] = self.params.as_slice() {
+            block.extend(args.into_iter().map(|arg| bind(name.clone(), *ty, arg)));
+        } else {
+            let temporary = |name: &str| format!("_{}_{name}", self.name);
+            for ((name, ty), arg) in self.params.iter().zip(args) {
+                block.push(bind(temporary(name), *ty, arg));
+            }
+            for (name, ty) in self.params.iter() {
+                let value = Expression::Variable {
+                    name: temporary(name),
+                    ty: *ty,
+                };
+                block.push(bind(name.clone(), *ty, value));
+            }
+        }
+        // The trailing return becomes the value of the block. Early returns are rejected by the
+        // macro for inline functions.
+        let (last, body) = match self.body.split_last() {
+            Some((last, body)) => (Some(last), body),
+            None => (None, &[][..]),
+        };
+        block.extend(body.iter().cloned());
+        match last {
+            Some(Statement::Return { value: Some(value) }) => {
+                block.push(Statement::ImplicitReturn {
+                    expression: value.clone(),
+                });
+            }
+            Some(Statement::Return { value: None }) | None => {}
+            Some(last) => block.push(last.clone()),
+        }
+
+        Expression::Block {
+            block,
+            ty: self.return_type,
+        }
+    }
+}
+
+/// Storage buffer parameter
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub name: String,
+    /// Element type
+    pub ty: IRType,
+    pub mutable: bool,
+}
+
+pub struct Call<T: SquareType> {
+    pub function: Function,
+    /// Arguments are type checked by the callee's generated `call`
+    pub args: Vec<Expression>,
+    pub _out: PhantomData<T>,
+}
+
+/// Argument of a function with array parameters. Storage buffers are bound globally by the entry
+/// point, so these functions can't be called from other kernels. Nothing implements it, which
+/// turns such calls into a readable error.
+#[diagnostic::on_unimplemented(
+    message = "functions with array parameters can't be called from other kernels",
+    label = "called here",
+    note = "arrays are bound as storage buffers of the entry point, so they can't be passed as arguments"
+)]
+pub trait ArrayParameters {}
+
+impl<T: SquareType> Expr for Call<T> {
+    type Output = T;
+
+    fn expression_untyped(&self) -> Expression {
+        if self.function.inline {
+            self.function.inline_call(self.args.clone())
+        } else {
+            Expression::Call {
+                function: Box::new(self.function.clone()),
+                args: self.args.clone(),
+                ty: <T as SquareType>::ir_type(),
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use super::{Expr, Expression, Float, Int, Numeric, Signed, SquareType};
+
+/// Built-in math functions. Arguments and result all have the same type, except for the `u32`
+/// exponent of `Pow` and the `i32` exponent of `Powi`.
+#[derive(Clone, Copy, Debug)]
+pub enum Intrinsic {
+    Sqrt,
+    Exp,
+    /// Natural logarithm
+    Ln,
+    Log2,
+    Sin,
+    Cos,
+    Tanh,
+    Powf,
+    /// Integer power
+    Pow,
+    /// Float raised to an integer power
+    Powi,
+    Abs,
+    Floor,
+    Ceil,
+    /// Rounds half-way cases away from zero like Rust's `round`
+    Round,
+    Min,
+    Max,
+    Clamp,
+    /// `self * a + b`
+    MulAdd,
+    Recip,
+}
+
+macro_rules! intrinsic {
+    ($name:ident, $bound:ident, $intrinsic:path, $($arg:tt),+) => {
+        pub struct $name<T: $bound>($(pub intrinsic!(@arg $arg, T)),+);
+
+        impl<T: $bound> Expr for $name<T> {
+            type Output = T;
+
+            fn expression_untyped(&self) -> Expression {
+                Expression::Intrinsic {
+                    intrinsic: $intrinsic,
+                    args: vec![$(self.$arg.expression_untyped()),+],
+                    ty: <T as SquareType>::ir_type(),
+                }
+            }
+        }
+    };
+    (@arg $arg:tt, $ty:ident) => {
+        Box<dyn Expr<Output = $ty>>
+    };
+}
+
+intrinsic!(SqrtExpr, Float, Intrinsic::Sqrt, 0);
+intrinsic!(ExpExpr, Float, Intrinsic::Exp, 0);
+intrinsic!(LnExpr, Float, Intrinsic::Ln, 0);
+intrinsic!(Log2Expr, Float, Intrinsic::Log2, 0);
+intrinsic!(SinExpr, Float, Intrinsic::Sin, 0);
+intrinsic!(CosExpr, Float, Intrinsic::Cos, 0);
+intrinsic!(TanhExpr, Float, Intrinsic::Tanh, 0);
+intrinsic!(PowfExpr, Float, Intrinsic::Powf, 0, 1);
+intrinsic!(AbsExpr, Signed, Intrinsic::Abs, 0);
+intrinsic!(FloorExpr, Float, Intrinsic::Floor, 0);
+intrinsic!(CeilExpr, Float, Intrinsic::Ceil, 0);
+intrinsic!(RoundExpr, Float, Intrinsic::Round, 0);
+intrinsic!(MinExpr, Numeric, Intrinsic::Min, 0, 1);
+intrinsic!(MaxExpr, Numeric, Intrinsic::Max, 0, 1);
+intrinsic!(ClampExpr, Numeric, Intrinsic::Clamp, 0, 1, 2);
+intrinsic!(MulAddExpr, Float, Intrinsic::MulAdd, 0, 1, 2);
+intrinsic!(RecipExpr, Float, Intrinsic::Recip, 0);
+
+/// `self.pow(exp)` on integers
+pub struct PowExpr<T: Int>(
+    pub Box<dyn Expr<Output = T>>,
+    pub Box<dyn Expr<Output = u32>>,
+);
+
+impl<T: Int> Expr for PowExpr<T> {
+    type Output = T;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Intrinsic {
+            intrinsic: Intrinsic::Pow,
+            args: vec![self.0.expression_untyped(), self.1.expression_untyped()],
+            ty: <T as SquareType>::ir_type(),
+        }
+    }
+}
+
+/// `self.powi(n)` on floats
+pub struct PowiExpr<T: Float>(
+    pub Box<dyn Expr<Output = T>>,
+    pub Box<dyn Expr<Output = i32>>,
+);
+
+impl<T: Float> Expr for PowiExpr<T> {
+    type Output = T;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Intrinsic {
+            intrinsic: Intrinsic::Powi,
+            args: vec![self.0.expression_untyped(), self.1.expression_untyped()],
+            ty: <T as SquareType>::ir_type(),
+        }
+    }
+}
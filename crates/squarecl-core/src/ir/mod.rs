@@ -1,11 +1,23 @@
+mod array;
+mod branch;
 mod expression;
+mod function;
+mod intrinsic;
 mod operator;
 mod statement;
+mod structs;
+mod tuple;
 mod types;
 
+pub use array::*;
+pub use branch::*;
 pub use expression::*;
+pub use function::*;
+pub use intrinsic::*;
 pub use operator::*;
 pub use statement::*;
+pub use structs::*;
+pub use tuple::*;
 pub use types::*;
 
 pub fn assert_valid_type<T: SquareType>() {}
@@ -1,9 +1,28 @@
+#[derive(Clone, Copy, Debug)]
 pub enum Operator {
     Add,
     Sub,
     Mul,
     Div,
+    /// Truncated remainder, the result has the sign of the left operand like Rust's `%`
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Short-circuiting `&&`. The right operand is only evaluated if the left one is `true`.
+    And,
+    /// Short-circuiting `||`. The right operand is only evaluated if the left one is `false`.
+    Or,
     Deref,
+    /// Logical not for `bool`, bitwise not for integers
     Not,
     Neg,
 }
@@ -1,5 +1,6 @@
 use super::{Expression, IRType};
 
+#[derive(Clone, Debug)]
 pub enum Statement {
     Local {
         variable: Box<Expression>,
@@ -12,4 +13,35 @@ pub enum Statement {
     ImplicitReturn {
         expression: Box<Expression>,
     },
+    /// Counted loop over a range. `end` is exclusive unless `inclusive` is set.
+    ForLoop {
+        label: Option<String>,
+        variable: Box<Expression>,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        step: Option<Box<Expression>>,
+        inclusive: bool,
+        block: Vec<Statement>,
+    },
+    WhileLoop {
+        label: Option<String>,
+        condition: Box<Expression>,
+        block: Vec<Statement>,
+    },
+    Loop {
+        label: Option<String>,
+        block: Vec<Statement>,
+    },
+    /// `return`, also used for the trailing expression of a function body
+    Return {
+        value: Option<Box<Expression>>,
+    },
+    /// `break`, optionally targeting a labelled loop. `None` targets the innermost loop.
+    Break {
+        label: Option<String>,
+    },
+    /// `continue`, optionally targeting a labelled loop. `None` targets the innermost loop.
+    Continue {
+        label: Option<String>,
+    },
 }
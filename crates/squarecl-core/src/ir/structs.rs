@@ -0,0 +1,37 @@
+use std::marker::PhantomData;
+
+use super::{Expr, Expression, SquareType};
+
+/// Structs with `#[derive(SquareType)]`. Field access in kernels goes through the generated
+/// `Fields`, which has a typed expression for each field.
+pub trait SquareStruct: SquareType {
+    type Fields;
+
+    fn fields(base: Expression) -> Self::Fields;
+}
+
+/// Fields of a struct expression, used by the macro to expand `base.field`
+pub fn struct_fields<E: Expr>(base: &E) -> <E::Output as SquareStruct>::Fields
+where
+    E::Output: SquareStruct,
+{
+    <E::Output as SquareStruct>::fields(base.expression_untyped())
+}
+
+pub struct FieldExpr<T: SquareType> {
+    pub base: Expression,
+    pub name: &'static str,
+    pub _type: PhantomData<T>,
+}
+
+impl<T: SquareType> Expr for FieldExpr<T> {
+    type Output = T;
+
+    fn expression_untyped(&self) -> Expression {
+        Expression::Field {
+            base: Box::new(self.base.clone()),
+            name: self.name.to_string(),
+            ty: <T as SquareType>::ir_type(),
+        }
+    }
+}
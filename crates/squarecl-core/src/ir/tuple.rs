@@ -0,0 +1,82 @@
+use std::{marker::PhantomData, sync::Mutex};
+
+use super::{Expr, Expression, FieldExpr, HostValue, IRType, SquareStruct, SquareType, StructType};
+
+/// Tuples are structs with the fields `_0`, `_1`, ... Their layouts are created on first use and
+/// shared by all tuples with the same element types.
+pub fn tuple_type(elements: &[IRType]) -> IRType {
+    static TUPLES: Mutex<Vec<&'static StructType>> = Mutex::new(Vec::new());
+
+    let name = [format!("Tuple{}", elements.len())]
+        .into_iter()
+        .chain(elements.iter().map(|ty| ty.to_string()))
+        .collect::<Vec<_>>()
+        .join("_");
+    let mut tuples = TUPLES.lock().unwrap();
+    if let Some(ty) = tuples.iter().find(|ty| ty.name == name) {
+        return IRType::Struct(ty);
+    }
+    let fields = elements
+        .iter()
+        .enumerate()
+        .map(|(index, ty)| (&*format!("_{index}").leak(), *ty))
+        .collect();
+    let ty = Box::leak(Box::new(StructType {
+        name: name.leak(),
+        fields,
+    }));
+    tuples.push(ty);
+    IRType::Struct(ty)
+}
+
+/// Tuple of element expressions, like `(a / b, a % b)`
+pub struct TupleExpr<T>(pub T);
+
+macro_rules! tuple {
+    ($($element:ident $index:tt),*) => {
+        impl<$($element: SquareType),*> SquareType for ($($element,)*) {
+            fn ir_type() -> IRType {
+                tuple_type(&[$(<$element as SquareType>::ir_type()),*])
+            }
+        }
+
+        impl<$($element: SquareType),*> SquareStruct for ($($element,)*) {
+            type Fields = ($(FieldExpr<$element>,)*);
+
+            fn fields(base: Expression) -> Self::Fields {
+                ($(FieldExpr {
+                    base: base.clone(),
+                    name: concat!("_", $index),
+                    _type: PhantomData,
+                },)*)
+            }
+        }
+
+        impl<$($element: SquareType + HostValue),*> HostValue for ($($element,)*) {
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                let IRType::Struct(ty) = <Self as SquareType>::ir_type() else {
+                    unreachable!("Tuples are structs");
+                };
+                ty.write_fields(&[$(&self.$index),*], bytes);
+            }
+        }
+
+        impl<$($element: Expr),*> Expr for TupleExpr<($($element,)*)>
+        where
+            $($element::Output: SquareType),*
+        {
+            type Output = ($($element::Output,)*);
+
+            fn expression_untyped(&self) -> Expression {
+                Expression::Tuple {
+                    elements: vec![$(self.0.$index.expression_untyped()),*],
+                    ty: <Self::Output as SquareType>::ir_type(),
+                }
+            }
+        }
+    };
+}
+
+tuple!(A 0, B 1);
+tuple!(A 0, B 1, C 2);
+tuple!(A 0, B 1, C 2, D 3);
@@ -1,15 +1,196 @@
+use std::{
+    fmt::Display,
+    ops::{
+        Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Div,
+        DivAssign, Mul, MulAssign, Neg, Not, Rem, RemAssign, Shl, ShlAssign, Shr, ShrAssign, Sub,
+        SubAssign,
+    },
+};
+
 #[derive(Debug, Clone, Copy)]
 pub enum IRType {
     Int(usize),
     UInt(usize),
     Float(usize),
+    Bool,
     Unit,
+    Struct(&'static StructType),
+}
+
+/// Layout of a struct with `#[derive(SquareType)]`
+#[derive(Debug)]
+pub struct StructType {
+    pub name: &'static str,
+    /// Fields in declaration order
+    pub fields: Vec<(&'static str, IRType)>,
+}
+
+impl IRType {
+    /// Size in bytes in a uniform buffer. Bools are stored as `u32`.
+    pub fn size(&self) -> usize {
+        match self {
+            IRType::Int(bits) | IRType::UInt(bits) | IRType::Float(bits) => bits / 8,
+            IRType::Bool => 4,
+            IRType::Unit => 0,
+            IRType::Struct(ty) => ty.layout().1,
+        }
+    }
+
+    /// Alignment in bytes in a uniform buffer
+    pub fn align(&self) -> usize {
+        match self {
+            IRType::Struct(ty) => ty.align(),
+            IRType::Unit => 1,
+            ty => ty.size(),
+        }
+    }
+}
+
+impl StructType {
+    /// Offsets of the fields and size of the struct in a uniform buffer. Fields that are structs
+    /// are aligned and padded to 16 bytes, which the uniform address space requires.
+    pub fn layout(&self) -> (Vec<usize>, usize) {
+        let mut offsets = Vec::new();
+        let mut end = 0usize;
+        for (_, field) in self.fields.iter() {
+            let (align, size) = member_layout(*field);
+            let offset = end.next_multiple_of(align);
+            offsets.push(offset);
+            end = offset + size;
+        }
+        (offsets, end.next_multiple_of(self.align()))
+    }
+
+    fn align(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|(_, field)| member_layout(*field).0)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Write the fields of a value of this struct at their offsets, padding to the size of the
+    /// struct
+    pub fn write_fields(&self, fields: &[&dyn HostValue], bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        let (offsets, size) = self.layout();
+        for (field, offset) in fields.iter().zip(offsets) {
+            bytes.resize(start + offset, 0);
+            field.write_bytes(bytes);
+        }
+        bytes.resize(start + size, 0);
+    }
+}
+
+/// Alignment and size of a struct member
+fn member_layout(ty: IRType) -> (usize, usize) {
+    match ty {
+        IRType::Struct(_) => (
+            ty.align().next_multiple_of(16),
+            ty.size().next_multiple_of(16),
+        ),
+        ty => (ty.align(), ty.size()),
+    }
+}
+
+/// Rust name of the type, used to tell apart specializations of generic functions
+impl Display for IRType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IRType::Int(size) => write!(f, "i{size}"),
+            IRType::UInt(size) => write!(f, "u{size}"),
+            IRType::Float(size) => write!(f, "f{size}"),
+            IRType::Bool => write!(f, "bool"),
+            IRType::Unit => write!(f, "unit"),
+            IRType::Struct(ty) => write!(f, "{}", ty.name),
+        }
+    }
 }
 
 pub trait SquareType {
     fn ir_type() -> IRType;
 }
 
+/// Values that can be passed to kernels as uniforms. They're written in the layout of the
+/// uniform address space, see `IRType::size` and `StructType::layout`.
+pub trait HostValue {
+    /// Append the bytes of the value. Structs are padded to their size, primitives aren't.
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_bytes(&mut bytes);
+        bytes
+    }
+}
+
+/// Types that support arithmetic and ordering. Bound for generic kernels.
+pub trait Numeric:
+    SquareType
+    + HostValue
+    + Copy
+    + Display
+    + PartialEq
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + RemAssign
+    + 'static
+{
+}
+
+/// Numeric types that can be negative
+pub trait Signed: Numeric + Neg<Output = Self> {}
+
+/// Integer types, with bitwise operators and total ordering
+pub trait Int:
+    Numeric
+    + Ord
+    + Not<Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+    + BitAndAssign
+    + BitOrAssign
+    + BitXorAssign
+    + ShlAssign<u32>
+    + ShrAssign<u32>
+{
+    fn pow(self, exp: u32) -> Self;
+}
+
+/// Floating point types. The methods mirror the inherent ones so generic kernels can call the
+/// math intrinsics.
+pub trait Float: Signed {
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn log2(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tanh(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn mul_add(self, a: Self, b: Self) -> Self;
+    fn recip(self) -> Self;
+}
+
 macro_rules! primitive {
     ($primitive:ident, $var_type:expr) => {
         impl SquareType for $primitive {
@@ -17,6 +198,12 @@ macro_rules! primitive {
                 $var_type
             }
         }
+
+        impl HostValue for $primitive {
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                bytes.extend(self.to_le_bytes());
+            }
+        }
     };
 }
 
@@ -28,3 +215,103 @@ primitive!(u32, IRType::UInt(32));
 primitive!(u64, IRType::UInt(64));
 primitive!(f32, IRType::Float(32));
 primitive!(f64, IRType::Float(64));
+
+impl SquareType for bool {
+    fn ir_type() -> IRType {
+        IRType::Bool
+    }
+}
+
+impl HostValue for bool {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        (*self as u32).write_bytes(bytes);
+    }
+}
+
+macro_rules! numeric {
+    ($($primitive:ident),*; $($signed:ident),*; $($int:ident),*) => {
+        $(impl Numeric for $primitive {})*
+        $(impl Signed for $signed {})*
+        $(impl Int for $int {
+            fn pow(self, exp: u32) -> Self {
+                $int::pow(self, exp)
+            }
+        })*
+    };
+}
+
+numeric!(
+    i16, i32, i64, u16, u32, u64, f32, f64;
+    i16, i32, i64, f32, f64;
+    i16, i32, i64, u16, u32, u64
+);
+
+macro_rules! float {
+    ($primitive:ident) => {
+        impl Float for $primitive {
+            fn sqrt(self) -> Self {
+                $primitive::sqrt(self)
+            }
+            fn exp(self) -> Self {
+                $primitive::exp(self)
+            }
+            fn ln(self) -> Self {
+                $primitive::ln(self)
+            }
+            fn log2(self) -> Self {
+                $primitive::log2(self)
+            }
+            fn sin(self) -> Self {
+                $primitive::sin(self)
+            }
+            fn cos(self) -> Self {
+                $primitive::cos(self)
+            }
+            fn tanh(self) -> Self {
+                $primitive::tanh(self)
+            }
+            fn powf(self, n: Self) -> Self {
+                $primitive::powf(self, n)
+            }
+            fn powi(self, n: i32) -> Self {
+                $primitive::powi(self, n)
+            }
+            fn abs(self) -> Self {
+                $primitive::abs(self)
+            }
+            fn floor(self) -> Self {
+                $primitive::floor(self)
+            }
+            fn ceil(self) -> Self {
+                $primitive::ceil(self)
+            }
+            fn round(self) -> Self {
+                $primitive::round(self)
+            }
+            fn min(self, other: Self) -> Self {
+                $primitive::min(self, other)
+            }
+            fn max(self, other: Self) -> Self {
+                $primitive::max(self, other)
+            }
+            fn clamp(self, min: Self, max: Self) -> Self {
+                $primitive::clamp(self, min, max)
+            }
+            fn mul_add(self, a: Self, b: Self) -> Self {
+                $primitive::mul_add(self, a, b)
+            }
+            fn recip(self) -> Self {
+                $primitive::recip(self)
+            }
+        }
+    };
+}
+
+float!(f32);
+float!(f64);
+
+impl SquareType for () {
+    fn ir_type() -> IRType {
+        IRType::Unit
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::atomic::{AtomicU32, Ordering};
 
 pub mod ir;
+pub mod runtime;
 
 /// Dummy code, would use real code in `cubecl`
 pub fn new_local_var() -> String {
@@ -0,0 +1,101 @@
+use std::marker::PhantomData;
+
+use crate::ir::{Function, HostValue, IRType, SquareType};
+
+/// Host side connection to a device that can compile and dispatch expanded kernels. Used by the
+/// `launch` and `launch_unchecked` functions generated by `#[square(launch)]`.
+pub trait ComputeClient {
+    /// Buffer allocated on the device
+    type Handle;
+
+    /// Compile `kernel` and dispatch it over `count` workgroups. `args` holds the runtime
+    /// arguments in declaration order.
+    fn execute(
+        &self,
+        kernel: Function,
+        count: WorkgroupCount,
+        args: Vec<KernelArg<'_, Self>>,
+        mode: ExecutionMode,
+    );
+}
+
+/// Number of workgroups to dispatch in each dimension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkgroupCount {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl WorkgroupCount {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Out of bounds array accesses are guarded by the backend
+    Checked,
+    /// Array accesses are emitted as is. Out of bounds accesses are undefined behaviour.
+    Unchecked,
+}
+
+/// Array argument of a kernel, bound to an `&Array<T>` or `&mut Array<T>` parameter
+pub struct ArrayArg<'a, C: ComputeClient + ?Sized, T: SquareType> {
+    pub handle: &'a C::Handle,
+    /// Number of elements
+    pub len: usize,
+    _type: PhantomData<T>,
+}
+
+impl<'a, C: ComputeClient + ?Sized, T: SquareType> ArrayArg<'a, C, T> {
+    pub fn new(handle: &'a C::Handle, len: usize) -> Self {
+        Self {
+            handle,
+            len,
+            _type: PhantomData,
+        }
+    }
+}
+
+/// Scalar or struct argument of a kernel, bound as a uniform
+pub struct ScalarArg<T: SquareType + HostValue> {
+    pub value: T,
+}
+
+impl<T: SquareType + HostValue> ScalarArg<T> {
+    pub fn new(value: T) -> Self {
+        Self { value }
+    }
+}
+
+/// Untyped argument, as passed to the client
+pub enum KernelArg<'a, C: ComputeClient + ?Sized> {
+    Array {
+        handle: &'a C::Handle,
+        len: usize,
+        ty: IRType,
+    },
+    /// Value in the layout of the uniform address space
+    Scalar { bytes: Vec<u8>, ty: IRType },
+}
+
+impl<'a, C: ComputeClient + ?Sized, T: SquareType> From<ArrayArg<'a, C, T>> for KernelArg<'a, C> {
+    fn from(arg: ArrayArg<'a, C, T>) -> Self {
+        KernelArg::Array {
+            handle: arg.handle,
+            len: arg.len,
+            ty: T::ir_type(),
+        }
+    }
+}
+
+impl<C: ComputeClient + ?Sized, T: SquareType + HostValue> From<ScalarArg<T>> for KernelArg<'_, C> {
+    fn from(arg: ScalarArg<T>) -> Self {
+        KernelArg::Scalar {
+            bytes: arg.value.to_bytes(),
+            ty: T::ir_type(),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{
+    spanned::Spanned, Expr, ExprBlock, ExprBreak, ExprContinue, ExprForLoop, ExprIf, ExprLit,
+    ExprLoop, ExprMatch, ExprReturn, ExprWhile, Lifetime, Lit, Pat, RangeLimits, Stmt, Token, Type,
+};
+
+use crate::{
+    expression::{expand_literal, Expression},
+    infer::Term,
+    reserved::check_name,
+    scope::Context,
+    statement::{parse_block, Statement},
+};
+
+pub fn expand_block(expr_block: ExprBlock, context: &mut Context) -> syn::Result<Expression> {
+    let span = expr_block.span();
+    if let Some(label) = expr_block.label {
+        Err(syn::Error::new_spanned(
+            label,
+            "Labelled blocks are not supported",
+        ))?;
+    }
+    let mut block = parse_block(expr_block.block, context)?;
+    // The trailing expression without a semicolon is the value of the block
+    let value = match block.pop() {
+        Some(Statement::Expression {
+            expression,
+            terminated: false,
+            ..
+        }) => Some(expression),
+        Some(statement) => {
+            block.push(statement);
+            None
+        }
+        None => None,
+    };
+    Ok(Expression::Block { block, value, span })
+}
+
+pub fn expand_if(expr_if: ExprIf, context: &mut Context) -> syn::Result<Expression> {
+    let span = expr_if.span();
+    let condition_tokens = expr_if.cond.clone();
+    let condition = Expression::from_expr(*expr_if.cond, context)?;
+    let then_block = parse_block(expr_if.then_branch, context)?;
+    let else_block = expr_if
+        .else_branch
+        .map(|(_, else_branch)| match *else_branch {
+            Expr::Block(block) => parse_block(block.block, context),
+            // `else if` is lowered to an else block containing only the nested branch
+            Expr::If(expr_if) => {
+                let span = expr_if.span();
+                let branch = expand_if(expr_if, context)?;
+                Ok(vec![Statement::Expression {
+                    expression: Box::new(branch),
+                    terminated: true,
+                    span,
+                }])
+            }
+            else_branch => Err(syn::Error::new_spanned(
+                else_branch,
+                "Unsupported else branch",
+            )),
+        })
+        .transpose()?;
+
+    // Only the taken branch is expanded when the condition is known at expansion time
+    if condition.is_comptime() {
+        return Ok(Expression::ComptimeBranch {
+            condition: condition_tokens,
+            then_block,
+            else_block,
+            span,
+        });
+    }
+
+    Ok(Expression::Branch {
+        condition: Box::new(condition),
+        then_block,
+        else_block,
+        span,
+    })
+}
+
+pub fn expand_match(expr_match: ExprMatch, context: &mut Context) -> syn::Result<Expression> {
+    let span = expr_match.span();
+    let value_span = expr_match.expr.span();
+    let value = Expression::from_expr(*expr_match.expr, context)?;
+    if let Some(ty) = value.ty().filter(|ty| !is_integer(ty)) {
+        Err(syn::Error::new(
+            value_span,
+            format!(
+                "Only integers can be matched on, found `{}`",
+                ty.to_token_stream()
+            ),
+        ))?;
+    }
+
+    let mut cases = Vec::new();
+    let mut default = None;
+    // WGSL rejects switches with duplicate case values
+    let mut seen = Vec::new();
+    for arm in expr_match.arms {
+        if let Some((_, guard)) = arm.guard {
+            Err(syn::Error::new_spanned(
+                guard,
+                "Match guards are not supported",
+            ))?;
+        }
+        // Arms after `_` are unreachable
+        if default.is_some() {
+            continue;
+        }
+        let values = match arm.pat {
+            Pat::Wild(_) => None,
+            Pat::Or(pat) => Some(
+                pat.cases
+                    .into_iter()
+                    .map(|pat| case_value(pat, &value, &mut seen, context))
+                    .collect::<syn::Result<Vec<_>>>()?,
+            ),
+            pat => Some(vec![case_value(pat, &value, &mut seen, context)?]),
+        };
+        let block = parse_arm_body(*arm.body, context)?;
+        match values {
+            Some(values) => cases.push((values, block)),
+            None => default = Some(block),
+        }
+    }
+    let default =
+        default.ok_or_else(|| syn::Error::new(span, "Matches in kernels require a `_` arm"))?;
+
+    Ok(Expression::Switch {
+        value: Box::new(value),
+        cases,
+        default,
+        span,
+    })
+}
+
+/// `seen` holds the values of the previous cases
+fn case_value(
+    pat: Pat,
+    value: &Expression,
+    seen: &mut Vec<(String, Span)>,
+    context: &mut Context,
+) -> syn::Result<Expression> {
+    let int = match &pat {
+        Pat::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        }) => int,
+        pat => Err(syn::Error::new_spanned(
+            pat,
+            "Only integer literals and `_` are supported as match patterns",
+        ))?,
+    };
+    let digits = int.base10_digits().to_string();
+    if let Some((_, first)) = seen.iter().find(|(other, _)| *other == digits) {
+        let mut error = syn::Error::new(
+            int.span(),
+            format!("Value `{digits}` is already matched by a previous arm"),
+        );
+        error.combine(syn::Error::new(*first, "First matched here"));
+        Err(error)?;
+    }
+    seen.push((digits, int.span()));
+    let case = expand_literal(Lit::Int(int.clone()), context)?;
+    context.unify(value.term(), case.term(), case.span());
+    Ok(case)
+}
+
+/// Arm bodies are either blocks or a single expression statement
+fn parse_arm_body(body: Expr, context: &mut Context) -> syn::Result<Vec<Statement>> {
+    match body {
+        Expr::Block(block) if block.label.is_none() => parse_block(block.block, context),
+        body => {
+            let span = body.span();
+            context.push_scope();
+            let statement = Statement::from_stmt(Stmt::Expr(body, Some(Token![;](span))), context);
+            context.pop_scope();
+            Ok(vec![statement?])
+        }
+    }
+}
+
+fn is_integer(ty: &Type) -> bool {
+    const INTEGERS: [&str; 10] = [
+        "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+    ];
+    match ty {
+        Type::Path(path) => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| INTEGERS.contains(&ident.to_string().as_str())),
+        _ => false,
+    }
+}
+
+pub fn expand_for_loop(for_loop: ExprForLoop, context: &mut Context) -> syn::Result<Statement> {
+    let span = for_loop.span();
+    let variable = match *for_loop.pat {
+        Pat::Ident(pat) => pat.ident,
+        pat => Err(syn::Error::new_spanned(
+            pat,
+            "Loop variable must be an identifier",
+        ))?,
+    };
+
+    // `(start..end).step_by(step)` carries the step as a method call on the range
+    let (range, step) = match *for_loop.expr {
+        Expr::MethodCall(call) if call.method == "step_by" && call.args.len() == 1 => {
+            let step = call.args.into_iter().next().unwrap();
+            (*call.receiver, Some(step))
+        }
+        range => (range, None),
+    };
+    let range = match range {
+        Expr::Paren(paren) => *paren.expr,
+        range => range,
+    };
+    let range = match range {
+        Expr::Range(range) => range,
+        range => Err(syn::Error::new_spanned(
+            range,
+            "Only `for` loops over ranges are supported",
+        ))?,
+    };
+    let inclusive = matches!(range.limits, RangeLimits::Closed(_));
+    let (start, end) = match (range.start, range.end) {
+        (Some(start), Some(end)) => (*start, *end),
+        _ => Err(syn::Error::new(
+            range.limits.span(),
+            "Loop ranges must have both a start and an end",
+        ))?,
+    };
+
+    let start = Expression::from_expr(start, context)?;
+    let end = Expression::from_expr(end, context)?;
+    let step = step
+        .map(|step| Expression::from_expr(step, context))
+        .transpose()?;
+    let label = for_loop.label.map(|label| label.name);
+
+    check_name(&variable.to_string(), variable.span())?;
+    // The loop variable lives in its own scope around the body
+    context.push_scope();
+    let id = context.push_variable(variable.clone(), None);
+    for bound in [Some(&start), Some(&end), step.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        context.unify(Term::Var(id), bound.term(), bound.span());
+    }
+    context.push_loop(label.clone());
+    let block = parse_block(for_loop.body, context)?;
+    context.pop_loop();
+    context.pop_scope();
+
+    Ok(Statement::ForLoop {
+        label,
+        variable,
+        id,
+        start: Box::new(start),
+        end: Box::new(end),
+        step: step.map(Box::new),
+        inclusive,
+        block,
+        ty: None,
+        span,
+    })
+}
+
+pub fn expand_while_loop(while_loop: ExprWhile, context: &mut Context) -> syn::Result<Statement> {
+    let span = while_loop.span();
+    let label = while_loop.label.map(|label| label.name);
+    let condition = Expression::from_expr(*while_loop.cond, context)?;
+
+    context.push_loop(label.clone());
+    let block = parse_block(while_loop.body, context)?;
+    context.pop_loop();
+
+    Ok(Statement::WhileLoop {
+        label,
+        condition: Box::new(condition),
+        block,
+        span,
+    })
+}
+
+pub fn expand_loop(expr_loop: ExprLoop, context: &mut Context) -> syn::Result<Statement> {
+    let span = expr_loop.span();
+    let label = expr_loop.label.map(|label| label.name);
+
+    context.push_loop(label.clone());
+    let block = parse_block(expr_loop.body, context)?;
+    context.pop_loop();
+
+    Ok(Statement::Loop { label, block, span })
+}
+
+pub fn expand_break(expr_break: ExprBreak, context: &mut Context) -> syn::Result<Statement> {
+    let span = expr_break.span();
+    if let Some(expr) = expr_break.expr {
+        Err(syn::Error::new_spanned(
+            expr,
+            "Breaking with a value is not supported",
+        ))?;
+    }
+    check_jump_target("break", expr_break.label.as_ref(), span, context)?;
+
+    Ok(Statement::Break {
+        label: expr_break.label,
+        span,
+    })
+}
+
+pub fn expand_continue(
+    expr_continue: ExprContinue,
+    context: &mut Context,
+) -> syn::Result<Statement> {
+    let span = expr_continue.span();
+    check_jump_target("continue", expr_continue.label.as_ref(), span, context)?;
+
+    Ok(Statement::Continue {
+        label: expr_continue.label,
+        span,
+    })
+}
+
+pub fn expand_return(expr_return: ExprReturn, context: &mut Context) -> syn::Result<Statement> {
+    let span = expr_return.span();
+    let value = expr_return
+        .expr
+        .map(|value| Expression::from_expr(*value, context))
+        .transpose()?
+        .map(Box::new);
+    context.push_return(span);
+    if let Some(value) = &value {
+        let ty = context.return_type().clone();
+        context.unify(
+            Term::Known(Box::new(ty.clone()), ty.span()),
+            value.term(),
+            value.span(),
+        );
+    }
+
+    Ok(Statement::Return {
+        value,
+        ty: context.return_type().clone(),
+        span,
+    })
+}
+
+fn check_jump_target(
+    keyword: &str,
+    label: Option<&Lifetime>,
+    span: Span,
+    context: &Context,
+) -> syn::Result<()> {
+    if !context.in_loop() {
+        Err(syn::Error::new(
+            span,
+            format!("`{keyword}` outside of a loop"),
+        ))?;
+    }
+    if let Some(label) = label.filter(|label| !context.has_loop_label(label)) {
+        Err(syn::Error::new_spanned(
+            label,
+            format!("Use of undeclared label `{label}`"),
+        ))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields};
+
+use crate::{prefix_ir, reserved::check_name};
+
+/// `#[derive(SquareType)]` for structs with named fields. The layout is built once and shared by
+/// all uses of the type.
+pub fn derive_square_type(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    if !input.generics.params.is_empty() {
+        Err(syn::Error::new_spanned(
+            &input.generics,
+            "Generic structs are not supported",
+        ))?;
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            fields => Err(syn::Error::new_spanned(
+                fields,
+                "Only structs with named fields are supported",
+            ))?,
+        },
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "Only structs are supported",
+        ))?,
+    };
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+    // Struct and field names are emitted as is in the generated shader
+    check_name(&name.to_string(), name.span())?;
+    for ident in idents.iter() {
+        check_name(&ident.to_string(), ident.span())?;
+    }
+    let names = idents.iter().map(|ident| ident.to_string());
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    let sq_type = prefix_ir(format_ident!("SquareType"));
+    let sq_struct = prefix_ir(format_ident!("SquareStruct"));
+    let host_value = prefix_ir(format_ident!("HostValue"));
+    let ir_type = prefix_ir(format_ident!("IRType"));
+    let struct_ty = prefix_ir(format_ident!("StructType"));
+    let expression = prefix_ir(format_ident!("Expression"));
+    let field_expr = prefix_ir(format_ident!("FieldExpr"));
+    let fields_name = format_ident!("__{name}Fields");
+    let name_str = name.to_string();
+    let field_names = idents.iter().map(|ident| ident.to_string());
+
+    Ok(quote! {
+        impl #sq_type for #name {
+            fn ir_type() -> #ir_type {
+                static TYPE: ::std::sync::OnceLock<#struct_ty> = ::std::sync::OnceLock::new();
+                #ir_type::Struct(TYPE.get_or_init(|| #struct_ty {
+                    name: #name_str,
+                    fields: vec![#((#names, <#types as #sq_type>::ir_type())),*],
+                }))
+            }
+        }
+
+        impl #host_value for #name {
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                let #ir_type::Struct(ty) = <Self as #sq_type>::ir_type() else {
+                    unreachable!("Derived types are structs");
+                };
+                ty.write_fields(&[#(&self.#idents),*], bytes);
+            }
+        }
+
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #vis struct #fields_name {
+            #(pub #idents: #field_expr<#types>),*
+        }
+
+        impl #sq_struct for #name {
+            type Fields = #fields_name;
+
+            fn fields(base: #expression) -> Self::Fields {
+                #fields_name {
+                    #(#idents: #field_expr {
+                        base: base.clone(),
+                        name: #field_names,
+                        _type: ::core::marker::PhantomData,
+                    }),*
+                }
+            }
+        }
+    })
+}
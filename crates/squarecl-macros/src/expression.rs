@@ -1,17 +1,18 @@
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned, ToTokens};
 use syn::{
-    parse::Parse,
-    spanned::Spanned,
-    visit::{visit_expr, Visit},
-    Expr, Ident, Lit, Pat, Path, Type,
+    parse_quote, spanned::Spanned, Expr, GenericArgument, Ident, Lit, Member, Path, PathArguments,
+    Type,
 };
 
 use crate::{
-    ir_type,
+    branch::{expand_block, expand_if, expand_match},
+    infer::Term,
+    intrinsic::{expand_call, expand_method_call, operand_count},
     operator::{parse_binop, parse_unop, Operator},
     prefix_ir,
     scope::Context,
+    statement::{generate_block, Statement},
 };
 
 pub enum Expression {
@@ -31,11 +32,17 @@ pub enum Expression {
     Variable {
         name: Ident,
         ty: Option<Type>,
+        /// Slot in the type table of the context
+        id: usize,
         span: Span,
     },
     Literal {
         value: Lit,
-        ty: Type,
+        /// Suffix or inferred type. Unconstrained unsuffixed literals are left to rustc, which
+        /// defaults them to `i32`/`f32`.
+        ty: Option<Type>,
+        /// Slot in the type table of the context, for unsuffixed literals
+        id: Option<usize>,
         span: Span,
     },
     Assigment {
@@ -44,16 +51,75 @@ pub enum Expression {
         ty: Option<Type>,
         span: Span,
     },
-    Init {
-        left: Box<Expression>,
-        right: Box<Expression>,
+    Cast {
+        from: Box<Expression>,
+        to: Type,
+        span: Span,
+    },
+    Intrinsic {
+        intrinsic: Path,
+        args: Vec<Expression>,
+        /// Explicit type from a `f32::sqrt(x)` style call
         ty: Option<Type>,
         span: Span,
     },
-    /// Tokens not relevant to parsing
-    Verbatim {
-        tokens: TokenStream,
+    /// Call to another `#[square]` function
+    Call {
+        function: Path,
+        args: Vec<Expression>,
+        span: Span,
+    },
+    /// Array element, `array[index]`
+    Index {
+        array: Box<Expression>,
+        index: Box<Expression>,
+        span: Span,
+    },
+    /// Field of a struct with `#[derive(SquareType)]` or element of a tuple
+    Field {
+        base: Box<Expression>,
+        field: Member,
+        span: Span,
+    },
+    /// Tuple of 2 to 4 elements
+    Tuple {
+        elements: Vec<Expression>,
+        span: Span,
+    },
+    /// `SharedMemory::<T>::new(size)` with a comptime size
+    SharedMemory {
+        ty: Option<Type>,
+        size: Box<Expr>,
+        span: Span,
+    },
+    Block {
+        block: Vec<Statement>,
+        value: Option<Box<Expression>>,
+        span: Span,
     },
+    Branch {
+        condition: Box<Expression>,
+        then_block: Vec<Statement>,
+        else_block: Option<Vec<Statement>>,
+        span: Span,
+    },
+    /// Branch on a condition known at expansion time, like a comptime parameter
+    ComptimeBranch {
+        condition: Box<Expr>,
+        then_block: Vec<Statement>,
+        else_block: Option<Vec<Statement>>,
+        span: Span,
+    },
+    Switch {
+        value: Box<Expression>,
+        cases: Vec<(Vec<Expression>, Vec<Statement>)>,
+        default: Vec<Statement>,
+        span: Span,
+    },
+    /// Comptime parameter, substituted as a literal
+    Comptime { name: Ident, ty: Type, span: Span },
+    /// Tokens not relevant to parsing
+    Verbatim { tokens: TokenStream },
 }
 
 impl Expression {
@@ -62,10 +128,12 @@ impl Expression {
             Expr::Assign(assign) => {
                 let span = assign.span();
                 let right = Self::from_expr(*assign.right, context)?;
+                let left = Self::from_expr(*assign.left, context)?;
+                context.unify(left.term(), right.term(), right.span());
                 Expression::Assigment {
                     span,
                     ty: right.ty(),
-                    left: Box::new(Self::from_expr(*assign.left, context)?),
+                    left: Box::new(left),
                     right: Box::new(right),
                 }
             }
@@ -73,34 +141,90 @@ impl Expression {
                 let span = binary.span();
                 let left = Self::from_expr(*binary.left, context)?;
                 let right = Self::from_expr(*binary.right, context)?;
-                let ty = left.ty().or(right.ty());
+                let operator = parse_binop(&binary.op)?;
+                if !operator.is_shift() {
+                    context.unify(left.term(), right.term(), right.span());
+                }
+                let ty = if operator.is_boolean() {
+                    Some(parse_quote![bool])
+                } else if operator.is_assign() {
+                    None
+                } else {
+                    left.ty().or(right.ty())
+                };
                 Expression::Binary {
                     span,
                     left: Box::new(left),
-                    operator: parse_binop(&binary.op)?,
+                    operator,
                     right: Box::new(right),
                     ty,
                 }
             }
-            Expr::Lit(literal) => {
-                let ty = lit_ty(&literal.lit)?;
-                Expression::Literal {
-                    span: literal.span(),
-                    value: literal.lit,
-                    ty,
+            Expr::Call(call) => expand_call(call, context)?,
+            Expr::MethodCall(call) => expand_method_call(call, context)?,
+            Expr::Cast(cast) => {
+                let span = cast.span();
+                let from = Self::from_expr(*cast.expr, context)?;
+                Expression::Cast {
+                    from: Box::new(from),
+                    to: *cast.ty,
+                    span,
+                }
+            }
+            Expr::Field(field) => {
+                let span = field.span();
+                let base = Self::from_expr(*field.base, context)?;
+                Expression::Field {
+                    base: Box::new(base),
+                    field: field.member,
+                    span,
+                }
+            }
+            Expr::Tuple(tuple) => {
+                let span = tuple.span();
+                if !(2..=4).contains(&tuple.elems.len()) {
+                    Err(syn::Error::new(span, "Tuples must have 2 to 4 elements"))?;
+                }
+                let elements = tuple
+                    .elems
+                    .into_iter()
+                    .map(|element| Self::from_expr(element, context))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                Expression::Tuple { elements, span }
+            }
+            Expr::Index(index) => {
+                let span = index.span();
+                let array = Self::from_expr(*index.expr, context)?;
+                let index = Self::from_expr(*index.index, context)?;
+                Expression::Index {
+                    array: Box::new(array),
+                    index: Box::new(index),
+                    span,
                 }
             }
+            Expr::Lit(literal) => expand_literal(literal.lit, context)?,
             Expr::Path(path) => {
                 let variable = path
                     .path
                     .get_ident()
-                    .and_then(|ident| Some((ident.clone(), context.variable_type(ident)?)));
-                if let Some((ident, ty)) = variable {
-                    Expression::Variable {
+                    .and_then(|ident| Some((ident.clone(), context.variable_id(ident)?)));
+                let comptime = path
+                    .path
+                    .get_ident()
+                    .and_then(|ident| Some((ident.clone(), context.comptime_type(ident)?)));
+                if let Some((name, ty)) = comptime {
+                    Expression::Comptime {
                         span: path.span(),
-                        name: ident.clone(),
+                        name,
                         ty,
                     }
+                } else if let Some((ident, id)) = variable {
+                    Expression::Variable {
+                        span: path.span(),
+                        ty: context.resolve_type(id),
+                        name: ident,
+                        id,
+                    }
                 } else {
                     // If it's not in the scope, it's not a managed local variable. Treat it as an
                     // external value like a Rust `const`
@@ -109,6 +233,8 @@ impl Expression {
                     }
                 }
             }
+            // Grouping is kept by the expression tree
+            Expr::Paren(paren) => Self::from_expr(*paren.expr, context)?,
             Expr::Unary(unary) => {
                 let span = unary.span();
                 let input = Self::from_expr(*unary.expr, context)?;
@@ -120,6 +246,9 @@ impl Expression {
                     ty,
                 }
             }
+            Expr::Block(expr_block) => expand_block(expr_block, context)?,
+            Expr::If(expr_if) => expand_if(expr_if, context)?,
+            Expr::Match(expr_match) => expand_match(expr_match, context)?,
             _ => Err(syn::Error::new_spanned(expr, "Unsupported expression"))?,
         };
         Ok(result)
@@ -130,10 +259,120 @@ impl Expression {
             Expression::Binary { ty, .. } => ty.clone(),
             Expression::Unary { ty, .. } => ty.clone(),
             Expression::Variable { ty, .. } => ty.clone(),
-            Expression::Literal { ty, .. } => Some(ty.clone()),
+            Expression::Literal { ty, .. } => ty.clone(),
             Expression::Assigment { ty, .. } => ty.clone(),
+            Expression::Comptime { ty, .. } => Some(ty.clone()),
             Expression::Verbatim { .. } => None,
-            Expression::Init { ty, .. } => ty.clone(),
+            Expression::Cast { to, .. } => Some(to.clone()),
+            Expression::Intrinsic {
+                intrinsic,
+                ty,
+                args,
+                ..
+            } => {
+                let operands = &args[..operand_count(intrinsic, args.len())];
+                ty.clone()
+                    .or_else(|| operands.iter().find_map(|arg| arg.ty()))
+            }
+            Expression::Call { .. } => None,
+            Expression::Index { array, .. } => array.ty().as_ref().and_then(array_elem),
+            Expression::Field { .. } => None,
+            Expression::Tuple { elements, .. } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.ty())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(parse_quote![(#(#elements,)*)])
+            }
+            Expression::SharedMemory { ty, .. } => {
+                let shared = prefix_ir(format_ident!("SharedMemory"));
+                ty.as_ref().map(|ty| parse_quote![#shared<#ty>])
+            }
+            Expression::Block { value, .. } => value.as_ref().and_then(|value| value.ty()),
+            Expression::Branch { .. } => None,
+            Expression::ComptimeBranch { .. } => None,
+            Expression::Switch { .. } => None,
+        }
+    }
+
+    /// Type of the expression for inference of the types of locals
+    pub fn term(&self) -> Term {
+        let known = |ty: Option<Type>| match ty {
+            Some(ty) => Term::Known(Box::new(ty), self.span()),
+            None => Term::Unknown,
+        };
+        match self {
+            Expression::Variable { id, .. } => Term::Var(*id),
+            Expression::Literal { id: Some(id), .. } => Term::Var(*id),
+            Expression::Binary {
+                left, operator, ty, ..
+            } => {
+                if operator.is_boolean() || operator.is_assign() {
+                    known(ty.clone())
+                } else {
+                    left.term()
+                }
+            }
+            Expression::Unary { input, .. } => input.term(),
+            Expression::Intrinsic { ty: None, args, .. } => args[0].term(),
+            Expression::Block {
+                value: Some(value), ..
+            } => value.term(),
+            _ => known(self.ty()),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Binary { span, .. }
+            | Expression::Unary { span, .. }
+            | Expression::Variable { span, .. }
+            | Expression::Literal { span, .. }
+            | Expression::Assigment { span, .. }
+            | Expression::Cast { span, .. }
+            | Expression::Intrinsic { span, .. }
+            | Expression::Call { span, .. }
+            | Expression::Index { span, .. }
+            | Expression::Field { span, .. }
+            | Expression::Tuple { span, .. }
+            | Expression::SharedMemory { span, .. }
+            | Expression::Block { span, .. }
+            | Expression::Branch { span, .. }
+            | Expression::ComptimeBranch { span, .. }
+            | Expression::Switch { span, .. }
+            | Expression::Comptime { span, .. } => *span,
+            Expression::Verbatim { tokens } => tokens.span(),
+        }
+    }
+
+    /// Whether this expression is a statement on its own and never produces a value, even
+    /// without a trailing semicolon.
+    pub fn is_statement(&self) -> bool {
+        match self {
+            Expression::Binary { operator, .. } => operator.is_assign(),
+            Expression::Block { value, .. } => value.is_none(),
+            Expression::Assigment { .. }
+            | Expression::Branch { .. }
+            | Expression::ComptimeBranch { .. }
+            | Expression::Switch { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the value is known at expansion time
+    pub fn is_comptime(&self) -> bool {
+        match self {
+            Expression::Literal { .. }
+            | Expression::Comptime { .. }
+            | Expression::Verbatim { .. } => true,
+            Expression::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => !operator.is_assign() && left.is_comptime() && right.is_comptime(),
+            Expression::Unary { input, .. } => input.is_comptime(),
+            _ => false,
         }
     }
 }
@@ -150,7 +389,7 @@ impl ToTokens for Expression {
                 span,
                 ..
             } => {
-                let span = span.clone();
+                let span = *span;
                 let ty = prefix_ir(format_ident!("{}Expr", operator.to_string()));
                 let ty_bin = prefix_ir(format_ident!("BinaryOp"));
                 quote_spanned! {span=>
@@ -167,7 +406,7 @@ impl ToTokens for Expression {
                 span,
                 ..
             } => {
-                let span = span.clone();
+                let span = *span;
                 let ty = prefix_ir(format_ident!("{}Expr", operator.to_string()));
                 let ty_un = prefix_ir(format_ident!("UnaryOp"));
                 quote_spanned! {span=>
@@ -177,17 +416,20 @@ impl ToTokens for Expression {
                     })
                 }
             }
-            Expression::Variable { name, span, ty } => {
-                let span = span.clone();
+            Expression::Variable { name, span, .. } => {
+                let span = *span;
                 quote_spanned! {span=>
                     #name
                 }
             }
-            Expression::Literal { value, span, ty } => {
-                let span = span.clone();
+            Expression::Literal {
+                value, span, ty, ..
+            } => {
+                let span = *span;
                 let ir_ty = prefix_ir(format_ident!("Literal"));
+                let ty = ty.as_ref().map(|ty| quote![::<#ty>]);
                 quote_spanned! {span=>
-                    #ir_ty {
+                    #ir_ty #ty {
                         value: #value
                     }
                 }
@@ -195,7 +437,7 @@ impl ToTokens for Expression {
             Expression::Assigment {
                 left, right, span, ..
             } => {
-                let span = span.clone();
+                let span = *span;
                 let ty = prefix_ir(format_ident!("Assignment"));
                 quote_spanned! {span=>
                     #ty {
@@ -204,19 +446,167 @@ impl ToTokens for Expression {
                     }
                 }
             }
-            Expression::Init {
-                left,
-                right,
+            Expression::Cast { from, to, span } => {
+                let span = *span;
+                let cast = prefix_ir(format_ident!("Cast"));
+                quote_spanned! {span=>
+                    #cast::<_, #to> {
+                        input: Box::new(#from),
+                        _to: ::core::marker::PhantomData,
+                    }
+                }
+            }
+            Expression::Intrinsic {
+                intrinsic,
+                args,
                 ty,
                 span,
             } => {
-                let span = span.clone();
-                let ir_type = ir_type("Initializer");
-                let ty = right.ty().map(|ty| quote![::<#ty>]);
+                let span = *span;
+                let ty = ty.as_ref().map(|ty| quote![::<#ty>]);
                 quote_spanned! {span=>
-                    #ir_type #ty {
-                        left: Box::new(#left),
-                        right: Box::new(#right)
+                    #intrinsic #ty (#(Box::new(#args)),*)
+                }
+            }
+            Expression::Call {
+                function,
+                args,
+                span,
+            } => {
+                let span = *span;
+                quote_spanned! {span=>
+                    #function::call(#(Box::new(#args)),*)
+                }
+            }
+            Expression::Index { array, index, span } => {
+                let span = *span;
+                let ty = prefix_ir(format_ident!("IndexExpr"));
+                quote_spanned! {span=>
+                    #ty {
+                        array: Box::new(#array),
+                        index: Box::new(#index),
+                    }
+                }
+            }
+            Expression::Field { base, field, span } => {
+                let span = *span;
+                let fields = prefix_ir(format_ident!("struct_fields"));
+                quote_spanned! {span=>
+                    #fields(&#base).#field
+                }
+            }
+            Expression::Tuple { elements, span } => {
+                let span = *span;
+                let tuple = prefix_ir(format_ident!("TupleExpr"));
+                quote_spanned! {span=>
+                    #tuple((#(#elements,)*))
+                }
+            }
+            Expression::SharedMemory { ty, size, span } => {
+                let span = *span;
+                let shared = prefix_ir(format_ident!("SharedMemoryExpr"));
+                let ty = ty.as_ref().map(|ty| quote![::<#ty>]);
+                quote_spanned! {span=>
+                    #shared #ty {
+                        size: #size,
+                        _type: ::core::marker::PhantomData,
+                    }
+                }
+            }
+            Expression::Block { block, value, span } => {
+                let span = *span;
+                let ty = prefix_ir(format_ident!("Block"));
+                let expr = prefix_ir(format_ident!("Expr"));
+                // Blocks without a value are `()`
+                let (value, out) = match value {
+                    Some(value) => (quote![Some(Box::new(#value))], quote![_]),
+                    None => (quote![None], quote![()]),
+                };
+                // The value is built in the same scope as the block's locals
+                quote_spanned! {span=>
+                    {
+                        let mut __statements = Vec::new();
+                        #(#block)*
+                        let __value: Option<Box<dyn #expr<Output = #out>>> = #value;
+                        #ty {
+                            statements: __statements,
+                            value: __value,
+                        }
+                    }
+                }
+            }
+            Expression::Branch {
+                condition,
+                then_block,
+                else_block,
+                span,
+            } => {
+                let span = *span;
+                let ty = prefix_ir(format_ident!("Branch"));
+                let then_block = generate_block(then_block);
+                let else_block = match else_block {
+                    Some(else_block) => {
+                        let else_block = generate_block(else_block);
+                        quote![Some(#else_block)]
+                    }
+                    None => quote![None],
+                };
+                quote_spanned! {span=>
+                    #ty {
+                        condition: Box::new(#condition),
+                        then_block: #then_block,
+                        else_block: #else_block
+                    }
+                }
+            }
+            Expression::ComptimeBranch {
+                condition,
+                then_block,
+                else_block,
+                span,
+            } => {
+                let span = *span;
+                let ty = prefix_ir(format_ident!("Block"));
+                let then_block = generate_block(then_block);
+                let else_block = match else_block {
+                    Some(else_block) => generate_block(else_block),
+                    None => quote![{ Vec::new() }],
+                };
+                quote_spanned! {span=>
+                    #ty::<()> {
+                        statements: if #condition #then_block else #else_block,
+                        value: None,
+                    }
+                }
+            }
+            Expression::Switch {
+                value,
+                cases,
+                default,
+                span,
+            } => {
+                let span = *span;
+                // Spanned at the value so a non-integer value is reported there
+                let ty = quote_spanned! {value.span()=> ::squarecl_core::ir::Switch};
+                let cases = cases.iter().map(|(values, block)| {
+                    let block = generate_block(block);
+                    quote![(vec![#(#values),*], #block)]
+                });
+                let default = generate_block(default);
+                quote_spanned! {span=>
+                    #ty {
+                        value: Box::new(#value),
+                        cases: vec![#(#cases),*],
+                        default: #default
+                    }
+                }
+            }
+            Expression::Comptime { name, span, .. } => {
+                let span = *span;
+                let ty = prefix_ir(format_ident!("Literal"));
+                quote_spanned! {span=>
+                    #ty {
+                        value: #name
                     }
                 }
             }
@@ -235,6 +625,29 @@ impl ToTokens for Expression {
     }
 }
 
+/// Unsuffixed numbers get a slot in the type table, so they can take their type from the
+/// expressions they're used with
+pub fn expand_literal(lit: Lit, context: &mut Context) -> syn::Result<Expression> {
+    let span = lit.span();
+    let ty = lit_ty(&lit)?;
+    let unsuffixed = match &lit {
+        Lit::Int(int) => int.suffix().is_empty(),
+        Lit::Float(float) => float.suffix().is_empty(),
+        _ => false,
+    };
+    let (ty, id) = if unsuffixed {
+        (None, Some(context.new_literal(ty, span)))
+    } else {
+        (Some(ty), None)
+    };
+    Ok(Expression::Literal {
+        value: lit,
+        ty,
+        id,
+        span,
+    })
+}
+
 fn lit_ty(lit: &Lit) -> syn::Result<Type> {
     let res = match lit {
         Lit::Int(int) => (!int.suffix().is_empty())
@@ -247,7 +660,7 @@ fn lit_ty(lit: &Lit) -> syn::Result<Type> {
             .map(|suffix| format_ident!("{suffix}"))
             .and_then(|ident| syn::parse2(quote![#ident]).ok())
             .unwrap_or_else(|| syn::parse2(quote![f32]).unwrap()),
-        Lit::Bool(_) => todo!("Not supported yet"),
+        Lit::Bool(_) => parse_quote![bool],
         lit => Err(syn::Error::new_spanned(
             lit,
             format!("Unsupported literal type: {lit:?}"),
@@ -256,6 +669,25 @@ fn lit_ty(lit: &Lit) -> syn::Result<Type> {
     Ok(res)
 }
 
+/// Element type of an `Array<T>` or `SharedMemory<T>`
+pub fn array_elem(ty: &Type) -> Option<Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path
+        .path
+        .segments
+        .last()
+        .filter(|seg| seg.ident == "Array" || seg.ident == "SharedMemory")?;
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
 pub fn generate_var(name: &Ident, ty: &Option<Type>, span: Span) -> TokenStream {
     let var = prefix_ir(format_ident!("Variable"));
     let name = name.to_token_stream().to_string();
@@ -0,0 +1,274 @@
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Type};
+
+use crate::{expression::Expression, statement::Statement};
+
+/// Type of an expression as far as the macro can tell. Anything the macro can't type is left to
+/// rustc.
+pub enum Term {
+    Known(Box<Type>, Span),
+    /// Local variable or unsuffixed literal
+    Var(usize),
+    Unknown,
+}
+
+/// Union-find table of the types of locals and unsuffixed literals. Each local gets a slot when
+/// it's declared, and uses of the local unify it with the types it's used with.
+#[derive(Default)]
+pub struct TypeTable {
+    slots: Vec<Slot>,
+    errors: Vec<syn::Error>,
+}
+
+struct Slot {
+    parent: usize,
+    ty: Option<(Type, Span)>,
+    /// Type of the unsuffixed literals in the slot and where the first one is
+    default: Option<(Type, Span)>,
+    /// Unified with an expression the macro can't type, like a call. The literal default
+    /// doesn't apply, since rustc may infer a different type.
+    opaque: bool,
+}
+
+impl TypeTable {
+    pub fn new_var(&mut self, ty: Option<Type>) -> usize {
+        let id = self.slots.len();
+        self.slots.push(Slot {
+            parent: id,
+            ty: ty.map(|ty| {
+                let span = ty.span();
+                (ty, span)
+            }),
+            default: None,
+            opaque: false,
+        });
+        id
+    }
+
+    /// Slot for an unsuffixed literal at `span`, which only falls back to `default` if nothing
+    /// else constrains it
+    pub fn new_literal(&mut self, default: Type, span: Span) -> usize {
+        let id = self.new_var(None);
+        self.slots[id].default = Some((default, span));
+        id
+    }
+
+    fn root(&self, mut id: usize) -> usize {
+        while self.slots[id].parent != id {
+            id = self.slots[id].parent;
+        }
+        id
+    }
+
+    /// Type of a local, falling back to the type of the literals it's initialized with. Locals
+    /// that depend on untyped expressions are left to rustc.
+    pub fn resolve(&self, id: usize) -> Option<Type> {
+        let slot = &self.slots[self.root(id)];
+        match &slot.ty {
+            Some((ty, _)) => Some(ty.clone()),
+            None if slot.opaque => None,
+            None => slot.default.as_ref().map(|(ty, _)| ty.clone()),
+        }
+    }
+
+    /// Type of a slot if it's constrained by anything other than literal defaults
+    pub fn resolve_known(&self, id: usize) -> Option<Type> {
+        let slot = &self.slots[self.root(id)];
+        slot.ty.as_ref().map(|(ty, _)| ty.clone())
+    }
+
+    /// Unify the type `found` at `span` with the `expected` type. The macro can't resolve paths,
+    /// so two known types, like `u32` and an alias of it, are never compared. Those conflicts are
+    /// left to rustc, and only integer and float literals ending up in the same slot are
+    /// reported at `span`, with a note where the expected literal is.
+    pub fn unify(&mut self, expected: Term, found: Term, span: Span) {
+        match (expected, found) {
+            (Term::Var(a), Term::Var(b)) => {
+                let (a, b) = (self.root(a), self.root(b));
+                if a == b {
+                    return;
+                }
+                if let (Some((expected, expected_span)), Some((found, _))) =
+                    (&self.slots[a].default, &self.slots[b].default)
+                {
+                    if is_float(expected) != is_float(found) {
+                        let kind = |ty| match is_float(ty) {
+                            true => "a float",
+                            false => "an integer",
+                        };
+                        let mut error = syn::Error::new(
+                            span,
+                            format!(
+                                "Mismatched literals: expected {}, found {}",
+                                kind(expected),
+                                kind(found)
+                            ),
+                        );
+                        error.combine(syn::Error::new(
+                            *expected_span,
+                            format!("Expected {} because of this", kind(expected)),
+                        ));
+                        self.errors.push(error);
+                    }
+                }
+                let slot = &mut self.slots[b];
+                slot.parent = a;
+                let (ty, default, opaque) = (slot.ty.take(), slot.default.take(), slot.opaque);
+                let root = &mut self.slots[a];
+                root.ty = root.ty.take().or(ty);
+                root.default = root.default.take().or(default);
+                root.opaque |= opaque;
+            }
+            (Term::Var(id), Term::Known(ty, span)) | (Term::Known(ty, span), Term::Var(id)) => {
+                let id = self.root(id);
+                self.slots[id].ty.get_or_insert((*ty, span));
+            }
+            (Term::Var(id), Term::Unknown) | (Term::Unknown, Term::Var(id)) => {
+                let id = self.root(id);
+                self.slots[id].opaque = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// All conflicts found while unifying, as a single error
+    pub fn errors(&self) -> Option<syn::Error> {
+        self.errors.iter().cloned().reduce(|mut errors, error| {
+            errors.combine(error);
+            errors
+        })
+    }
+}
+
+/// Whether the default type of a literal is a float. Literal defaults are always `i32` or `f32`.
+fn is_float(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("f32"))
+}
+
+/// Fill in the inferred types of the locals once the whole body has been parsed
+pub fn resolve_block(block: &mut [Statement], table: &TypeTable) {
+    for statement in block {
+        match statement {
+            Statement::Local { left, init, ty, .. } => {
+                if let Expression::Variable { id, .. } = &**left {
+                    *ty = ty.take().or_else(|| table.resolve(*id));
+                }
+                resolve_expression(left, table);
+                if let Some(init) = init {
+                    resolve_expression(init, table);
+                }
+            }
+            Statement::Expression { expression, .. } => resolve_expression(expression, table),
+            Statement::ForLoop {
+                id,
+                start,
+                end,
+                step,
+                block,
+                ty,
+                ..
+            } => {
+                *ty = ty.take().or_else(|| table.resolve(*id));
+                resolve_expression(start, table);
+                resolve_expression(end, table);
+                if let Some(step) = step {
+                    resolve_expression(step, table);
+                }
+                resolve_block(block, table);
+            }
+            Statement::WhileLoop {
+                condition, block, ..
+            } => {
+                resolve_expression(condition, table);
+                resolve_block(block, table);
+            }
+            Statement::Loop { block, .. } => resolve_block(block, table),
+            Statement::Return { value, .. } => {
+                if let Some(value) = value {
+                    resolve_expression(value, table);
+                }
+            }
+            Statement::Break { .. } | Statement::Continue { .. } => {}
+        }
+    }
+}
+
+fn resolve_expression(expression: &mut Expression, table: &TypeTable) {
+    match expression {
+        Expression::Variable { id, ty, .. } => {
+            *ty = ty.take().or_else(|| table.resolve(*id));
+        }
+        Expression::Binary { left, right, .. } | Expression::Assigment { left, right, .. } => {
+            resolve_expression(left, table);
+            resolve_expression(right, table);
+        }
+        Expression::Index { array, index, .. } => {
+            resolve_expression(array, table);
+            resolve_expression(index, table);
+        }
+        Expression::Unary { input, .. } => resolve_expression(input, table),
+        Expression::Cast { from, .. } => resolve_expression(from, table),
+        Expression::Field { base, .. } => resolve_expression(base, table),
+        Expression::Intrinsic { args, .. } | Expression::Call { args, .. } => {
+            for arg in args {
+                resolve_expression(arg, table);
+            }
+        }
+        Expression::Tuple { elements, .. } => {
+            for element in elements {
+                resolve_expression(element, table);
+            }
+        }
+        Expression::Block { block, value, .. } => {
+            resolve_block(block, table);
+            if let Some(value) = value {
+                resolve_expression(value, table);
+            }
+        }
+        Expression::Branch {
+            condition,
+            then_block,
+            else_block,
+            ..
+        } => {
+            resolve_expression(condition, table);
+            resolve_block(then_block, table);
+            if let Some(else_block) = else_block {
+                resolve_block(else_block, table);
+            }
+        }
+        Expression::ComptimeBranch {
+            then_block,
+            else_block,
+            ..
+        } => {
+            resolve_block(then_block, table);
+            if let Some(else_block) = else_block {
+                resolve_block(else_block, table);
+            }
+        }
+        Expression::Switch {
+            value,
+            cases,
+            default,
+            ..
+        } => {
+            resolve_expression(value, table);
+            for (values, block) in cases {
+                for value in values {
+                    resolve_expression(value, table);
+                }
+                resolve_block(block, table);
+            }
+            resolve_block(default, table);
+        }
+        Expression::Literal { ty, id, .. } => {
+            if let Some(id) = id {
+                *ty = table.resolve_known(*id);
+            }
+        }
+        Expression::SharedMemory { .. }
+        | Expression::Comptime { .. }
+        | Expression::Verbatim { .. } => {}
+    }
+}
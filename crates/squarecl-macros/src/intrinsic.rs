@@ -0,0 +1,256 @@
+use proc_macro2::Span;
+use quote::format_ident;
+use syn::{
+    punctuated::Punctuated, spanned::Spanned, Expr, ExprCall, ExprMethodCall, GenericArgument,
+    Path, PathArguments, Token, Type, TypePath,
+};
+
+use crate::{expression::Expression, prefix_ir, scope::Context};
+
+/// Built-in math functions as `(name, IR expression, argument count including self)`
+const INTRINSICS: &[(&str, &str, usize)] = &[
+    ("sqrt", "SqrtExpr", 1),
+    ("exp", "ExpExpr", 1),
+    ("ln", "LnExpr", 1),
+    ("log2", "Log2Expr", 1),
+    ("sin", "SinExpr", 1),
+    ("cos", "CosExpr", 1),
+    ("tanh", "TanhExpr", 1),
+    ("powf", "PowfExpr", 2),
+    ("pow", "PowExpr", 2),
+    ("powi", "PowiExpr", 2),
+    ("abs", "AbsExpr", 1),
+    ("floor", "FloorExpr", 1),
+    ("ceil", "CeilExpr", 1),
+    ("round", "RoundExpr", 1),
+    ("min", "MinExpr", 2),
+    ("max", "MaxExpr", 2),
+    ("clamp", "ClampExpr", 3),
+    ("mul_add", "MulAddExpr", 3),
+    ("recip", "RecipExpr", 1),
+];
+
+/// Scalar types whose intrinsics can be called through the type, like `f32::max(a, b)`
+const SCALARS: &[&str] = &[
+    "f32", "f64", "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+];
+
+/// Intrinsics whose second argument is an integer exponent, so only the first argument has the
+/// type of the result
+const EXPONENT_INTRINSICS: &[&str] = &["PowExpr", "PowiExpr"];
+
+/// Number of leading arguments that have the same type as the result
+pub fn operand_count(intrinsic: &Path, args: usize) -> usize {
+    let name = &intrinsic.segments.last().unwrap().ident;
+    match EXPONENT_INTRINSICS.iter().any(|exponent| name == exponent) {
+        true => 1,
+        false => args,
+    }
+}
+
+/// `x.sqrt()`
+pub fn expand_method_call(call: ExprMethodCall, context: &mut Context) -> syn::Result<Expression> {
+    let span = call.span();
+    if let Some(turbofish) = call.turbofish {
+        Err(syn::Error::new_spanned(
+            turbofish,
+            "Generic arguments are not supported on intrinsics",
+        ))?;
+    }
+    let args = Some(*call.receiver).into_iter().chain(call.args);
+    expand_intrinsic(
+        &call.method.to_string(),
+        call.method.span(),
+        None,
+        args,
+        span,
+        context,
+    )
+}
+
+/// `f32::sqrt(x)`, `SharedMemory::<f32>::new(256)` or a call to another `#[square]` function
+/// like `gelu(x)`
+pub fn expand_call(call: ExprCall, context: &mut Context) -> syn::Result<Expression> {
+    let span = call.span();
+    if matches!(&*call.func, Expr::Path(path) if is_shared_memory(&path.path)) {
+        Err(syn::Error::new_spanned(
+            &call,
+            "Shared memory can only be allocated as the initializer of a `let`",
+        ))?;
+    }
+    let path = match *call.func {
+        Expr::Path(path) if path.qself.is_none() => path.path,
+        func => Err(syn::Error::new_spanned(func, "Unsupported function call"))?,
+    };
+    if is_intrinsic(&path, context) {
+        let (name, ty) = split_path(path);
+        return expand_intrinsic(
+            &name.to_string(),
+            name.span(),
+            Some(ty),
+            call.args,
+            span,
+            context,
+        );
+    }
+
+    // Calls are expanded eagerly, so a recursive function would never finish expanding
+    if is_recursive(&path, context) {
+        Err(syn::Error::new_spanned(
+            &path,
+            "Recursive functions are not supported",
+        ))?;
+    }
+
+    let args = call
+        .args
+        .into_iter()
+        .map(|arg| Expression::from_expr(arg, context))
+        .collect::<syn::Result<Vec<_>>>()?;
+    Ok(Expression::Call {
+        function: path,
+        args,
+        span,
+    })
+}
+
+/// Whether the path names an intrinsic on a scalar type, like `f32::max` or `F::sqrt` for a type
+/// parameter `F`. Other paths ending in an intrinsic name, like `helpers::max`, are calls.
+fn is_intrinsic(path: &Path, context: &Context) -> bool {
+    let [ty, name] = match path.segments.iter().collect::<Vec<_>>()[..] {
+        [ty, name] if path.leading_colon.is_none() && ty.arguments.is_none() => [ty, name],
+        _ => return false,
+    };
+    let is_scalar = SCALARS.iter().any(|scalar| ty.ident == scalar);
+    (is_scalar || context.is_type_param(&ty.ident))
+        && INTRINSICS
+            .iter()
+            .any(|(intrinsic, _, _)| name.ident == intrinsic)
+}
+
+/// Whether the path names the function being expanded, like `f` or `self::f` inside `f`
+fn is_recursive(path: &Path, context: &Context) -> bool {
+    let segments = path.segments.iter().collect::<Vec<_>>();
+    let name = match segments[..] {
+        [name] => name,
+        [module, name] if module.ident == "self" => name,
+        _ => return false,
+    };
+    path.leading_colon.is_none() && context.is_function(&name.ident)
+}
+
+/// Initializer of a `let`, the only place where shared memory can be allocated since backends
+/// declare it as a named global
+pub fn expand_init(expr: Expr, context: &mut Context) -> syn::Result<Expression> {
+    let Expr::Call(call) = expr else {
+        return Expression::from_expr(expr, context);
+    };
+    let span = call.span();
+    match *call.func {
+        Expr::Path(path) if path.qself.is_none() && is_shared_memory(&path.path) => {
+            expand_shared_memory(path.path, call.args, span, context)
+        }
+        func => expand_call(
+            ExprCall {
+                func: Box::new(func),
+                ..call
+            },
+            context,
+        ),
+    }
+}
+
+fn is_shared_memory(path: &Path) -> bool {
+    let mut segments = path.segments.iter().rev();
+    segments
+        .next()
+        .is_some_and(|segment| segment.ident == "new")
+        && segments
+            .next()
+            .is_some_and(|segment| segment.ident == "SharedMemory")
+}
+
+/// `SharedMemory::<T>::new(size)`. The size is emitted as is, so it must be known at expansion
+/// time.
+fn expand_shared_memory(
+    path: Path,
+    args: Punctuated<Expr, Token![,]>,
+    span: Span,
+    context: &mut Context,
+) -> syn::Result<Expression> {
+    let ty = match &path.segments[path.segments.len() - 2].arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        }),
+        _ => None,
+    };
+    let [size] = args
+        .into_iter()
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| syn::Error::new(span, "`SharedMemory::new` takes the number of elements"))?;
+    if !Expression::from_expr(size.clone(), context)?.is_comptime() {
+        Err(syn::Error::new_spanned(
+            &size,
+            "Shared memory size must be known at expansion time",
+        ))?;
+    }
+    Ok(Expression::SharedMemory {
+        ty,
+        size: Box::new(size),
+        span,
+    })
+}
+
+fn expand_intrinsic(
+    name: &str,
+    name_span: Span,
+    ty: Option<Type>,
+    args: impl IntoIterator<Item = Expr>,
+    span: Span,
+    context: &mut Context,
+) -> syn::Result<Expression> {
+    let (_, intrinsic, arg_count) = INTRINSICS
+        .iter()
+        .find(|(intrinsic, _, _)| *intrinsic == name)
+        .ok_or_else(|| syn::Error::new(name_span, format!("Unsupported function `{name}`")))?;
+    let args = args
+        .into_iter()
+        .map(|arg| Expression::from_expr(arg, context))
+        .collect::<syn::Result<Vec<_>>>()?;
+    if args.len() != *arg_count {
+        Err(syn::Error::new(
+            span,
+            format!(
+                "`{name}` takes {arg_count} argument(s), found {}",
+                args.len()
+            ),
+        ))?;
+    }
+
+    Ok(Expression::Intrinsic {
+        intrinsic: prefix_ir(format_ident!("{intrinsic}")),
+        args,
+        ty,
+        span,
+    })
+}
+
+/// Split `f32::sqrt` into `sqrt` and `f32`
+fn split_path(mut path: Path) -> (syn::Ident, Type) {
+    let name = path.segments.pop().unwrap().into_value().ident;
+    // Remove trailing `::`
+    let segments = path
+        .segments
+        .into_iter()
+        .collect::<Punctuated<_, Token![::]>>();
+    let ty = Type::Path(TypePath {
+        qself: None,
+        path: Path {
+            leading_colon: path.leading_colon,
+            segments,
+        },
+    });
+    (name, ty)
+}
@@ -1,29 +1,85 @@
 use std::cell::RefCell;
 
+use proc_macro2::Span;
 use quote::{format_ident, quote, quote_spanned, ToTokens};
 use syn::{
-    parse::Parse, spanned::Spanned, visit::Visit, FnArg, Ident, ItemFn, Pat, PatType, Type,
+    parse::Parse, parse_quote, spanned::Spanned, Attribute, Expr, ExprLit, FnArg, GenericArgument,
+    GenericParam, Generics, Ident, ItemFn, Lit, LitStr, Pat, PathArguments, ReturnType, Type,
     Visibility,
 };
 
-use crate::{expression::generate_var, prefix_ir, scope::Context, statement::Statement};
+use crate::{
+    expression::array_elem,
+    infer::{resolve_block, Term},
+    prefix_ir,
+    reserved::check_name,
+    runtime_type,
+    scope::Context,
+    statement::{generate_block, parse_block, Statement},
+};
 
 pub struct Kernel {
     visibility: Visibility,
     name: Ident,
-    parameters: Vec<(Ident, Type)>,
+    generics: Generics,
+    parameters: Vec<Parameter>,
+    returns: Type,
     statements: Vec<Statement>,
+    /// Inline the body into callers instead of emitting a separate function
+    inline: bool,
+    /// Generate `launch`
+    launch: bool,
+    /// Generate `launch_unchecked`
+    launch_unchecked: bool,
+    /// Dimensions of the workgroup, evaluated at expansion time
+    workgroup_size: Option<[Expr; 3]>,
+    /// Name of the entry point, if it isn't the function name
+    entry: Option<LitStr>,
+    /// First `return` that isn't the last statement of the body
+    early_return: Option<Span>,
 
     context: RefCell<Context>,
 }
 
+struct Parameter {
+    ident: Ident,
+    ty: Type,
+    /// Known at expansion time and substituted as a literal
+    comptime: bool,
+    /// `&Array<T>` or `&mut Array<T>`, bound as a storage buffer. `ty` is the referenced type.
+    storage: bool,
+    mutable: bool,
+}
+
 impl Parse for Kernel {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let mut context = Context::default();
 
         let function: ItemFn = input.parse()?;
         let name = function.sig.ident;
+        check_name(&name.to_string(), name.span())?;
         let vis = function.vis;
+        let generics = function.sig.generics;
+        if let Some(param) = generics
+            .params
+            .iter()
+            .find(|param| !matches!(param, GenericParam::Type(_)))
+        {
+            Err(syn::Error::new_spanned(
+                param,
+                "Only type parameters are supported on kernels",
+            ))?;
+        }
+        let type_params = generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect();
+        context.set_function(name.clone(), type_params);
+        let returns = match function.sig.output {
+            ReturnType::Default => parse_quote![()],
+            ReturnType::Type(_, ty) => *ty,
+        };
+        context.set_return_type(returns.clone());
         let parameters = function
             .sig
             .inputs
@@ -36,43 +92,131 @@ impl Parse for Kernel {
                 )),
             })
             .collect::<Result<Vec<_>, _>>()?;
-        let variables = parameters
+        let parameters = parameters
             .into_iter()
-            .map(|input| -> syn::Result<(Ident, Type)> {
-                let ty = *input.ty;
+            .map(|input| -> syn::Result<Parameter> {
+                let comptime = input.attrs.iter().any(is_comptime_attr);
+                let (ty, storage, mutable) = match *input.ty {
+                    Type::Reference(reference) => {
+                        if !is_array(&reference.elem) {
+                            Err(syn::Error::new_spanned(
+                                &reference,
+                                "Only arrays can be passed by reference",
+                            ))?;
+                        }
+                        if comptime {
+                            Err(syn::Error::new_spanned(
+                                &reference,
+                                "Arrays can't be comptime",
+                            ))?;
+                        }
+                        match array_elem(&reference.elem) {
+                            Some(elem) if is_bool(&elem) => Err(syn::Error::new_spanned(
+                                &reference,
+                                "`bool` can't be stored in arrays, use `Array<u32>` instead",
+                            ))?,
+                            Some(elem) if contains_array(&elem) => Err(syn::Error::new_spanned(
+                                &reference,
+                                "Arrays can't contain arrays",
+                            ))?,
+                            _ => {}
+                        }
+                        let mutable = reference.mutability.is_some();
+                        (*reference.elem, true, mutable)
+                    }
+                    ty if contains_array(&ty) => Err(syn::Error::new_spanned(
+                        ty,
+                        "Arrays can only be passed by reference",
+                    ))?,
+                    ty => (ty, false, false),
+                };
                 let ident = match *input.pat {
+                    // Scalars are bound as read-only uniforms and WGSL function parameters can't
+                    // be assigned either
+                    Pat::Ident(ident) if ident.mutability.is_some() => {
+                        Err(syn::Error::new_spanned(
+                            ident,
+                            "Parameters can't be mutable, copy them into a `let mut` instead",
+                        ))?
+                    }
                     Pat::Ident(ident) => ident.ident,
                     input => Err(syn::Error::new_spanned(
                         input,
                         "kernel input should be ident",
                     ))?,
                 };
-                Ok((ident, ty))
+                // Comptime parameters are replaced by their values
+                if !comptime {
+                    check_name(&ident.to_string(), ident.span())?;
+                }
+                Ok(Parameter {
+                    ident,
+                    ty,
+                    comptime,
+                    storage,
+                    mutable,
+                })
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        for param in parameters.iter().filter(|param| param.comptime) {
+            context.push_comptime(param.ident.clone(), param.ty.clone());
+        }
         context.extend(
-            variables
+            parameters
                 .iter()
-                .cloned()
-                .map(|(ident, ty)| (ident, Some(ty))),
+                .filter(|param| !param.comptime)
+                .map(|param| (param.ident.clone(), Some(param.ty.clone()))),
         );
-        context.push_scope(); // Push function local scope
-
-        let statements = function
-            .block
-            .stmts
-            .into_iter()
-            .map(|statement| Statement::from_stmt(statement, &mut context))
-            .collect::<Result<Vec<_>, _>>()?;
+        // Function body is parsed in its own local scope
+        let mut statements = parse_block(*function.block, &mut context)?;
+        // The trailing expression is returned like an explicit `return`
+        let trailing_return = match statements.pop() {
+            Some(Statement::Expression {
+                expression,
+                terminated: false,
+                span,
+            }) => {
+                context.unify(
+                    Term::Known(Box::new(returns.clone()), returns.span()),
+                    expression.term(),
+                    expression.span(),
+                );
+                statements.push(Statement::Return {
+                    value: Some(expression),
+                    ty: returns.clone(),
+                    span,
+                });
+                false
+            }
+            Some(statement) => {
+                let is_return = matches!(statement, Statement::Return { .. });
+                statements.push(statement);
+                is_return
+            }
+            None => false,
+        };
+        if let Some(errors) = context.types().errors() {
+            Err(errors)?;
+        }
+        resolve_block(&mut statements, context.types());
 
-        context.pop_scope(); // Pop function local scope
+        let early_returns = context.returns().len() - trailing_return as usize;
+        let early_return = context.returns()[..early_returns].first().copied();
 
         Ok(Kernel {
             visibility: vis,
             name,
-            parameters: variables,
+            generics,
+            parameters,
+            returns,
             statements,
+            inline: false,
+            launch: false,
+            launch_unchecked: false,
+            workgroup_size: None,
+            entry: None,
+            early_return,
             context: RefCell::new(context),
         })
     }
@@ -83,12 +227,12 @@ impl ToTokens for Kernel {
         let vis = &self.visibility;
         let name = &self.name;
         let global_vars = self.context.borrow().current_scope().generate_vars();
-        let statements = &self.statements;
-        let statement_ty = prefix_ir(format_ident!("Statement"));
+        let function_ty = prefix_ir(format_ident!("Function"));
+        let function = self.generate_function();
         let input_checks = self
             .parameters
             .iter()
-            .map(|(_, ty)| {
+            .map(|Parameter { ty, .. }| {
                 let span = ty.span();
                 let check = prefix_ir(format_ident!("assert_valid_type"));
                 quote_spanned! {span=>
@@ -96,26 +240,392 @@ impl ToTokens for Kernel {
                 }
             })
             .collect::<Vec<_>>();
+        let call = self.generate_call();
+        let launch = self.launch.then(|| self.generate_launch(false));
+        let launch_unchecked = self.launch_unchecked.then(|| self.generate_launch(true));
+        let comptime_params = self
+            .parameters
+            .iter()
+            .filter(|param| param.comptime)
+            .map(|Parameter { ident, ty, .. }| quote![#ident: #ty]);
+        let (generics, _, where_clause) = self.generics.split_for_impl();
         tokens.extend(quote! {
             #vis mod #name {
                 use super::*;
 
-                fn __check_inputs() {
+                fn __check_inputs #generics () #where_clause {
                     #(#input_checks)*
                 }
 
                 #[allow(unused_braces)]
-                pub fn expand
-                /* Const generics could be used to supplement comptime */
-                (/* Comptime values would go here */) -> Vec<#statement_ty> {
+                pub fn expand #generics (#(#comptime_params),*) -> #function_ty #where_clause {
                     #(#global_vars)*
-                    {
-                        let mut __statements = Vec::new();
-                        #(#statements)*
-                        __statements
+                    #function
+                }
+
+                #call
+
+                #launch
+
+                #launch_unchecked
+            }
+        });
+    }
+}
+
+impl Kernel {
+    /// Inline the function into its callers
+    pub fn set_inline(&mut self) -> syn::Result<()> {
+        if let Some(span) = self.early_return {
+            Err(syn::Error::new(
+                span,
+                "Early returns are not supported in inline functions",
+            ))?;
+        }
+        self.inline = true;
+        Ok(())
+    }
+
+    /// Generate a host side `launch` function, or `launch_unchecked` if `unchecked` is set
+    pub fn set_launch(&mut self, option: &Ident, unchecked: bool) -> syn::Result<()> {
+        if self.inline {
+            Err(syn::Error::new_spanned(
+                option,
+                "Inline functions can't be launched",
+            ))?;
+        }
+        if !matches!(&self.returns, Type::Tuple(tuple) if tuple.elems.is_empty()) {
+            Err(syn::Error::new_spanned(
+                &self.returns,
+                "Launched kernels can't return a value",
+            ))?;
+        }
+        if unchecked {
+            self.launch_unchecked = true;
+        } else {
+            self.launch = true;
+        }
+        Ok(())
+    }
+
+    /// Configure the compute shader entry point of the kernel
+    pub fn set_entry_point(
+        &mut self,
+        workgroup_size: Option<[Expr; 3]>,
+        entry: Option<LitStr>,
+    ) -> syn::Result<()> {
+        if self.inline {
+            let span = match (&workgroup_size, &entry) {
+                (Some(dims), _) => dims[0].span(),
+                (None, Some(entry)) => entry.span(),
+                (None, None) => Span::call_site(),
+            };
+            Err(syn::Error::new(
+                span,
+                "Inline functions can't be entry points",
+            ))?;
+        }
+        if !matches!(&self.returns, Type::Tuple(tuple) if tuple.elems.is_empty()) {
+            Err(syn::Error::new_spanned(
+                &self.returns,
+                "Entry points can't return a value",
+            ))?;
+        }
+        for dim in workgroup_size.iter().flatten() {
+            if let Expr::Lit(ExprLit {
+                lit: Lit::Int(int), ..
+            }) = dim
+            {
+                if int.base10_parse::<u32>()? == 0 {
+                    Err(syn::Error::new_spanned(
+                        dim,
+                        "Workgroup size dimensions must be nonzero",
+                    ))?;
+                }
+            }
+        }
+        self.workgroup_size = workgroup_size;
+        self.entry = entry;
+        Ok(())
+    }
+
+    /// Generate the expanded IR function
+    fn generate_function(&self) -> proc_macro2::TokenStream {
+        let returns = &self.returns;
+        let sq_type = prefix_ir(format_ident!("SquareType"));
+        let function_ty = prefix_ir(format_ident!("Function"));
+        let block = generate_block(&self.statements);
+        let inline = self.inline;
+        // Literal dimensions are checked by the macro, others may depend on comptime values, so
+        // they can only be checked on expansion
+        let workgroup_size = match &self.workgroup_size {
+            Some(dims) if dims.iter().all(|dim| matches!(dim, Expr::Lit(_))) => {
+                quote![[#(#dims),*]]
+            }
+            Some(dims) => quote! {{
+                let __workgroup_size: [u32; 3] = [#(#dims),*];
+                assert!(
+                    __workgroup_size.iter().all(|dim| *dim > 0),
+                    "Workgroup size dimensions must be nonzero, got {:?}",
+                    __workgroup_size,
+                );
+                __workgroup_size
+            }},
+            None => quote![[1, 1, 1]],
+        };
+        let entry = match &self.entry {
+            Some(entry) => quote![Some(#entry.to_string())],
+            None => quote![None],
+        };
+
+        let binding_ty = prefix_ir(format_ident!("Binding"));
+        let ir_type = prefix_ir(format_ident!("IRType"));
+
+        let params = self.runtime_params().filter(|param| !param.storage).map(
+            |Parameter { ident, ty, .. }| {
+                let name = ident.to_string();
+                quote![(#name.to_string(), <#ty as #sq_type>::ir_type())]
+            },
+        );
+        let bindings = self
+            .parameters
+            .iter()
+            .filter(|param| param.storage)
+            .map(|param| {
+                let Parameter {
+                    ident, ty, mutable, ..
+                } = param;
+                let name = ident.to_string();
+                quote! {
+                    #binding_ty {
+                        name: #name.to_string(),
+                        ty: <#ty as #sq_type>::ir_type(),
+                        mutable: #mutable,
                     }
                 }
+            });
+        let generics = self
+            .generics
+            .type_params()
+            .map(|param| {
+                let name = param.ident.to_string();
+                let ident = &param.ident;
+                quote![(#name.to_string(), <#ident as #sq_type>::ir_type())]
+            })
+            .collect::<Vec<_>>();
+        // Each specialization is a separate function, so generic types and comptime values are
+        // part of the name
+        let comptime = self
+            .parameters
+            .iter()
+            .filter(|param| param.comptime)
+            .map(|param| &param.ident)
+            .collect::<Vec<_>>();
+        let name = self.name.to_string();
+        let name = if comptime.is_empty() && generics.is_empty() {
+            quote![#name.to_string()]
+        } else {
+            quote! {
+                #function_ty::mangle(
+                    [#name.to_string()]
+                        .into_iter()
+                        .chain(__generics.iter().map(|(_, ty)| ty.to_string()))
+                        .chain([#(#comptime.to_string()),*]),
+                )
+            }
+        };
+
+        quote! {{
+            let __generics: Vec<(String, #ir_type)> = vec![#(#generics),*];
+            #function_ty {
+                name: #name,
+                generics: __generics,
+                params: vec![#(#params),*],
+                bindings: vec![#(#bindings),*],
+                return_type: <#returns as #sq_type>::ir_type(),
+                body: #block,
+                inline: #inline,
+                workgroup_size: #workgroup_size,
+                entry: #entry,
+                bounds_checks: true,
+            }
+        }}
+    }
+
+    /// Generate `call`, which type checks the arguments of a call from another kernel. Comptime
+    /// arguments must be literals or comptime values of the caller. Storage buffers are global,
+    /// so functions with array parameters get a `call` that fails with an explanation instead.
+    fn generate_call(&self) -> proc_macro2::TokenStream {
+        let returns = &self.returns;
+        let expr = prefix_ir(format_ident!("Expr"));
+        let literal = prefix_ir(format_ident!("Literal"));
+        let call_ty = prefix_ir(format_ident!("Call"));
+        let (generics, turbofish, where_clause) = self.generics.split_for_impl();
+
+        if self.parameters.iter().any(|param| param.storage) {
+            let array_params = prefix_ir(format_ident!("ArrayParameters"));
+            let params = (0..self.parameters.len()).map(|i| match i {
+                0 => quote![_: impl #array_params],
+                _ => quote![_: impl Sized],
+            });
+            return quote! {
+                pub fn call #generics (#(#params),*) -> #call_ty<#returns> #where_clause {
+                    unreachable!("`ArrayParameters` isn't implemented")
+                }
+            };
+        }
+
+        let params = self.parameters.iter().map(|param| {
+            let Parameter { ident, ty, .. } = param;
+            if param.comptime {
+                quote![#ident: Box<#literal<#ty>>]
+            } else {
+                quote![#ident: Box<dyn #expr<Output = #ty>>]
+            }
+        });
+        let comptime_args = self
+            .parameters
+            .iter()
+            .filter(|param| param.comptime)
+            .map(|Parameter { ident, .. }| quote![#ident.value]);
+        let args = self
+            .runtime_params()
+            .map(|Parameter { ident, .. }| quote![#expr::expression_untyped(&*#ident)]);
+
+        let turbofish = turbofish.as_turbofish();
+        // Callers box every argument since they can't tell which parameters are comptime
+        quote! {
+            #[allow(clippy::boxed_local)]
+            pub fn call #generics (#(#params),*) -> #call_ty<#returns> #where_clause {
+                #call_ty {
+                    function: expand #turbofish (#(#comptime_args),*),
+                    args: vec![#(#args),*],
+                    _out: ::core::marker::PhantomData,
+                }
+            }
+        }
+    }
+
+    /// Generate `launch` or `launch_unchecked`, which expand the kernel and dispatch it with typed
+    /// arguments. The unchecked variant lets the backend skip bounds checks, so it's unsafe.
+    fn generate_launch(&self, unchecked: bool) -> proc_macro2::TokenStream {
+        let client_ty = runtime_type("ComputeClient");
+        let count_ty = runtime_type("WorkgroupCount");
+        let array_arg = runtime_type("ArrayArg");
+        let scalar_arg = runtime_type("ScalarArg");
+        let kernel_arg = runtime_type("KernelArg");
+        let mode = runtime_type("ExecutionMode");
+        let indexed = prefix_ir(format_ident!("Indexed"));
+
+        let params = self.parameters.iter().map(|param| {
+            let Parameter { ident, ty, .. } = param;
+            if param.comptime {
+                quote![#ident: #ty]
+            } else if param.storage {
+                quote![#ident: #array_arg<'_, __Client, <#ty as #indexed>::Item>]
+            } else {
+                quote![#ident: #scalar_arg<#ty>]
+            }
+        });
+        let comptime_args = self
+            .parameters
+            .iter()
+            .filter(|param| param.comptime)
+            .map(|param| &param.ident);
+        let args = self
+            .runtime_params()
+            .map(|Parameter { ident, .. }| quote![#kernel_arg::from(#ident)]);
+
+        let mut generics = self.generics.clone();
+        generics.params.push(parse_quote![__Client: #client_ty]);
+        let (generics, _, where_clause) = generics.split_for_impl();
+        let (_, turbofish, _) = self.generics.split_for_impl();
+        let turbofish = turbofish.as_turbofish();
+        let (name, unsafety, mode, bounds_checks) = if unchecked {
+            (
+                format_ident!("launch_unchecked"),
+                Some(quote![unsafe]),
+                quote![#mode::Unchecked],
+                false,
+            )
+        } else {
+            (format_ident!("launch"), None, quote![#mode::Checked], true)
+        };
+        let safety = unchecked.then(|| {
+            quote! {
+                /// # Safety
+                ///
+                /// Array accesses aren't bounds checked, out of bounds accesses are undefined
+                /// behaviour.
             }
         });
+
+        quote! {
+            #safety
+            #[allow(clippy::too_many_arguments)]
+            pub #unsafety fn #name #generics (
+                __client: &__Client,
+                __count: #count_ty,
+                #(#params),*
+            ) #where_clause {
+                let mut __kernel = expand #turbofish (#(#comptime_args),*);
+                __kernel.bounds_checks = #bounds_checks;
+                __client.execute(__kernel, __count, vec![#(#args),*], #mode);
+            }
+        }
+    }
+
+    fn runtime_params(&self) -> impl Iterator<Item = &Parameter> {
+        self.parameters.iter().filter(|param| !param.comptime)
+    }
+}
+
+fn is_array(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Array"),
+        _ => false,
+    }
+}
+
+/// Whether the type is or contains an array, like `(Array<f32>, u32)`
+fn contains_array(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.iter().any(|segment| {
+            segment.ident == "Array"
+                || match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| match arg {
+                        GenericArgument::Type(ty) => contains_array(ty),
+                        _ => false,
+                    }),
+                    _ => false,
+                }
+        }),
+        Type::Tuple(tuple) => tuple.elems.iter().any(contains_array),
+        Type::Array(array) => contains_array(&array.elem),
+        Type::Reference(reference) => contains_array(&reference.elem),
+        Type::Paren(paren) => contains_array(&paren.elem),
+        Type::Group(group) => contains_array(&group.elem),
+        _ => false,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+fn is_comptime_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("comptime")
+}
+
+/// `#[comptime]` is only meaningful to the macro, so it's removed from the emitted function
+pub fn strip_comptime_attrs(function: &mut ItemFn) {
+    for input in function.sig.inputs.iter_mut() {
+        if let FnArg::Typed(input) = input {
+            input.attrs.retain(|attr| !is_comptime_attr(attr));
+        }
     }
 }
@@ -1,68 +1,125 @@
-#![allow(unused)]
+use std::collections::HashSet;
 
-use std::{cell::LazyCell, collections::HashSet};
-
-use kernel::Kernel;
+use kernel::{strip_comptime_attrs, Kernel};
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::{format_ident, quote};
-use statement::Statement;
 use syn::{
-    parse::Parse, parse_macro_input, punctuated::Punctuated, Ident, ItemFn, Path, PathSegment,
-    Token,
+    parse::Parse, parse_macro_input, punctuated::Punctuated, DeriveInput, Expr, ExprLit, Ident,
+    ItemFn, Lit, LitStr, Meta, MetaNameValue, Path, Token,
 };
 
+mod branch;
+mod derive;
 mod expression;
+mod infer;
+mod intrinsic;
 mod kernel;
 mod operator;
+mod reserved;
 mod scope;
 mod statement;
 
-const IR_PREFIX: &'static str = "::squarecl_core::ir::";
-const IR_PATH: LazyCell<Path> = LazyCell::new(|| {
+fn ir_path() -> Path {
     let span = Span::call_site();
     let mut path = Path::from(format_ident!("squarecl_core"));
     path.segments.push(format_ident!("ir").into());
     path.leading_colon = Some(Token![::](span));
     path
-});
+}
 
 pub(crate) fn prefix_ir(ident: Ident) -> Path {
-    let mut path = IR_PATH.clone();
+    let mut path = ir_path();
     path.segments.push(ident.into());
     path
 }
 pub(crate) fn ir_type(ty: &str) -> Path {
     let ident = format_ident!("{ty}");
-    let mut path = IR_PATH.clone();
+    let mut path = ir_path();
     path.segments.push(ident.into());
     path
 }
 
+/// Path to a host side type in `squarecl_core::runtime`
+pub(crate) fn runtime_type(ty: &str) -> Path {
+    let span = Span::call_site();
+    let mut path = Path::from(format_ident!("squarecl_core"));
+    path.segments.push(format_ident!("runtime").into());
+    path.segments.push(format_ident!("{ty}").into());
+    path.leading_colon = Some(Token![::](span));
+    path
+}
+
 struct Args {
-    /// This would hold launch, launch_unchecked
+    /// `launch`, `launch_unchecked` and `inline`
     options: HashSet<Ident>,
+    /// `workgroup_size = (x, y, z)`. Dimensions are evaluated at expansion time, so they can use
+    /// comptime parameters.
+    workgroup_size: Option<[Expr; 3]>,
+    /// `entry = "name"`
+    entry: Option<LitStr>,
+}
+
+impl Args {
+    fn has(&self, option: &str) -> bool {
+        self.options.iter().any(|ident| ident == option)
+    }
 }
 
 impl Parse for Args {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        // If more complex parsing is needed, it would go here.
-        let acceptable_values = ["launch", "launch_unchecked"];
-        let options: Result<HashSet<Ident>, _> =
-            Punctuated::<Ident, Token![,]>::parse_terminated(input)?
-                .into_iter()
-                .map(|ident| {
-                    if acceptable_values.contains(&ident.to_string().as_str()) {
-                        Ok(ident)
-                    } else {
-                        Err(syn::Error::new_spanned(
-                            ident,
-                            "Only `launch` or `launch_unchecked` are allowed.",
-                        ))
+        let acceptable_values = ["launch", "launch_unchecked", "inline"];
+        let unsupported = |meta: &Meta| {
+            syn::Error::new_spanned(
+                meta,
+                "Only `launch`, `launch_unchecked`, `inline`, `workgroup_size = (x, y, z)` or \
+                 `entry = \"name\"` are allowed.",
+            )
+        };
+        let mut args = Args {
+            options: HashSet::new(),
+            workgroup_size: None,
+            entry: None,
+        };
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            match &meta {
+                Meta::Path(path) => match path.get_ident() {
+                    Some(ident) if acceptable_values.contains(&ident.to_string().as_str()) => {
+                        args.options.insert(ident.clone());
                     }
-                })
-                .collect();
-        Ok(Args { options: options? })
+                    _ => Err(unsupported(&meta))?,
+                },
+                Meta::NameValue(MetaNameValue { path, value, .. })
+                    if path.is_ident("workgroup_size") =>
+                {
+                    let dims = match value {
+                        Expr::Tuple(tuple) if tuple.elems.len() == 3 => tuple.elems.clone(),
+                        value => Err(syn::Error::new_spanned(
+                            value,
+                            "Expected `workgroup_size = (x, y, z)`",
+                        ))?,
+                    };
+                    let dims: Vec<_> = dims.into_iter().collect();
+                    args.workgroup_size = Some(dims.try_into().unwrap());
+                }
+                Meta::NameValue(MetaNameValue { path, value, .. }) if path.is_ident("entry") => {
+                    let name = match value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(name),
+                            ..
+                        }) if name.parse::<Ident>().is_ok() => name.clone(),
+                        value => Err(syn::Error::new_spanned(
+                            value,
+                            "Expected an identifier string, like `entry = \"main\"`",
+                        ))?,
+                    };
+                    reserved::check_name(&name.value(), name.span())?;
+                    args.entry = Some(name);
+                }
+                meta => Err(unsupported(meta))?,
+            }
+        }
+        Ok(args)
     }
 }
 
@@ -70,11 +127,40 @@ impl Parse for Args {
 pub fn square(args: TokenStream, input: TokenStream) -> TokenStream {
     let args = parse_macro_input!(args as Args);
     let in_2 = input.clone();
-    let kernel = parse_macro_input!(in_2 as Kernel);
-    let function = parse_macro_input!(input as ItemFn);
+    let mut kernel = parse_macro_input!(in_2 as Kernel);
+    if args.has("inline") {
+        if let Err(err) = kernel.set_inline() {
+            return err.to_compile_error().into();
+        }
+    }
+    for option in args.options.iter() {
+        let unchecked = option == "launch_unchecked";
+        if option != "launch" && !unchecked {
+            continue;
+        }
+        if let Err(err) = kernel.set_launch(option, unchecked) {
+            return err.to_compile_error().into();
+        }
+    }
+    if args.workgroup_size.is_some() || args.entry.is_some() {
+        if let Err(err) = kernel.set_entry_point(args.workgroup_size, args.entry) {
+            return err.to_compile_error().into();
+        }
+    }
+    let mut function = parse_macro_input!(input as ItemFn);
+    strip_comptime_attrs(&mut function);
 
     TokenStream::from(quote! {
         #function
         #kernel
     })
 }
+
+/// Implement `SquareType` for a struct, so it can be used in kernels and its fields accessed
+#[proc_macro_derive(SquareType)]
+pub fn derive_square_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::derive_square_type(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
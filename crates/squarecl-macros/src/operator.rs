@@ -1,7 +1,5 @@
-use std::fmt::Display;
-
 use derive_more::derive::Display;
-use syn::{visit::Visit, BinOp, UnOp};
+use syn::{BinOp, UnOp};
 
 #[derive(Debug, Clone, Copy, Display)]
 pub enum Operator {
@@ -9,17 +7,107 @@ pub enum Operator {
     Sub,
     Mul,
     Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    RemAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShlAssign,
+    ShrAssign,
     Deref,
     Not,
     Neg,
 }
 
+impl Operator {
+    /// Whether the operator always produces a `bool`
+    pub fn is_boolean(&self) -> bool {
+        matches!(
+            self,
+            Operator::Eq
+                | Operator::Ne
+                | Operator::Lt
+                | Operator::Le
+                | Operator::Gt
+                | Operator::Ge
+                | Operator::And
+                | Operator::Or
+        )
+    }
+
+    /// Whether the operator is a shift, where the amount can have a different type
+    pub fn is_shift(&self) -> bool {
+        matches!(
+            self,
+            Operator::Shl | Operator::Shr | Operator::ShlAssign | Operator::ShrAssign
+        )
+    }
+
+    /// Whether the operator is a compound assignment like `+=`
+    pub fn is_assign(&self) -> bool {
+        matches!(
+            self,
+            Operator::AddAssign
+                | Operator::SubAssign
+                | Operator::MulAssign
+                | Operator::DivAssign
+                | Operator::RemAssign
+                | Operator::BitAndAssign
+                | Operator::BitOrAssign
+                | Operator::BitXorAssign
+                | Operator::ShlAssign
+                | Operator::ShrAssign
+        )
+    }
+}
+
 pub fn parse_binop(op: &BinOp) -> syn::Result<Operator> {
     let op = match op {
         BinOp::Add(_) => Operator::Add,
         BinOp::Sub(_) => Operator::Sub,
         BinOp::Mul(_) => Operator::Mul,
         BinOp::Div(_) => Operator::Div,
+        BinOp::Rem(_) => Operator::Rem,
+        BinOp::BitAnd(_) => Operator::BitAnd,
+        BinOp::BitOr(_) => Operator::BitOr,
+        BinOp::BitXor(_) => Operator::BitXor,
+        BinOp::Shl(_) => Operator::Shl,
+        BinOp::Shr(_) => Operator::Shr,
+        BinOp::Eq(_) => Operator::Eq,
+        BinOp::Ne(_) => Operator::Ne,
+        BinOp::Lt(_) => Operator::Lt,
+        BinOp::Le(_) => Operator::Le,
+        BinOp::Gt(_) => Operator::Gt,
+        BinOp::Ge(_) => Operator::Ge,
+        BinOp::And(_) => Operator::And,
+        BinOp::Or(_) => Operator::Or,
+        BinOp::AddAssign(_) => Operator::AddAssign,
+        BinOp::SubAssign(_) => Operator::SubAssign,
+        BinOp::MulAssign(_) => Operator::MulAssign,
+        BinOp::DivAssign(_) => Operator::DivAssign,
+        BinOp::RemAssign(_) => Operator::RemAssign,
+        BinOp::BitAndAssign(_) => Operator::BitAndAssign,
+        BinOp::BitOrAssign(_) => Operator::BitOrAssign,
+        BinOp::BitXorAssign(_) => Operator::BitXorAssign,
+        BinOp::ShlAssign(_) => Operator::ShlAssign,
+        BinOp::ShrAssign(_) => Operator::ShrAssign,
         _ => Err(syn::Error::new_spanned(op, "Unsupported operator"))?,
     };
     Ok(op)
@@ -0,0 +1,193 @@
+use proc_macro2::Span;
+
+/// Keywords, type names and reserved words of WGSL that are valid Rust identifiers. Rust keywords
+/// are left out, since they can't be used as names anyway.
+const RESERVED: &[&str] = &[
+    "CompileShader",
+    "ComputeShader",
+    "DomainShader",
+    "GeometryShader",
+    "Hullshader",
+    "NULL",
+    "active",
+    "alias",
+    "alignas",
+    "alignof",
+    "array",
+    "asm",
+    "asm_fragment",
+    "atomic",
+    "attribute",
+    "auto",
+    "binding_array",
+    "bitcast",
+    "bool",
+    "case",
+    "cast",
+    "catch",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "coherent",
+    "column_major",
+    "common",
+    "compile",
+    "compile_fragment",
+    "concept",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continuing",
+    "debugger",
+    "decltype",
+    "default",
+    "delete",
+    "demote",
+    "demote_to_helper",
+    "discard",
+    "dynamic_cast",
+    "enable",
+    "explicit",
+    "export",
+    "extends",
+    "external",
+    "f16",
+    "f32",
+    "fallthrough",
+    "filter",
+    "finally",
+    "friend",
+    "from",
+    "fxgroup",
+    "get",
+    "goto",
+    "groupshared",
+    "handle",
+    "highp",
+    "i32",
+    "i64",
+    "implements",
+    "import",
+    "inline",
+    "inout",
+    "instanceof",
+    "interface",
+    "layout",
+    "lowp",
+    "macro_rules",
+    "mat2x2",
+    "mat2x3",
+    "mat2x4",
+    "mat3x2",
+    "mat3x3",
+    "mat3x4",
+    "mat4x2",
+    "mat4x3",
+    "mat4x4",
+    "mediump",
+    "meta",
+    "module",
+    "mutable",
+    "namespace",
+    "new",
+    "nil",
+    "noexcept",
+    "noinline",
+    "nointerpolation",
+    "noperspective",
+    "null",
+    "nullptr",
+    "of",
+    "operator",
+    "package",
+    "packoffset",
+    "partition",
+    "pass",
+    "patch",
+    "pixelfragment",
+    "precise",
+    "precision",
+    "premerge",
+    "protected",
+    "ptr",
+    "public",
+    "readonly",
+    "regardless",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "resource",
+    "restrict",
+    "sampler",
+    "sampler_comparison",
+    "set",
+    "shared",
+    "signed",
+    "sizeof",
+    "smooth",
+    "snorm",
+    "static_assert",
+    "static_cast",
+    "std",
+    "subroutine",
+    "switch",
+    "target",
+    "template",
+    "texture_1d",
+    "texture_2d",
+    "texture_2d_array",
+    "texture_3d",
+    "texture_cube",
+    "texture_cube_array",
+    "texture_depth_2d",
+    "texture_depth_2d_array",
+    "texture_depth_cube",
+    "texture_depth_cube_array",
+    "texture_depth_multisampled_2d",
+    "texture_multisampled_2d",
+    "texture_storage_1d",
+    "texture_storage_2d",
+    "texture_storage_2d_array",
+    "texture_storage_3d",
+    "this",
+    "thread_local",
+    "throw",
+    "typedef",
+    "typeid",
+    "typename",
+    "u32",
+    "u64",
+    "union",
+    "unless",
+    "unorm",
+    "using",
+    "var",
+    "varying",
+    "vec2",
+    "vec3",
+    "vec4",
+    "volatile",
+    "wgsl",
+    "with",
+    "writeonly",
+];
+
+/// Reject names that can't be used as WGSL identifiers, since kernel, parameter and local names
+/// are emitted as is
+pub fn check_name(name: &str, span: Span) -> syn::Result<()> {
+    if RESERVED.contains(&name) {
+        Err(syn::Error::new(
+            span,
+            format!("`{name}` is a reserved word in WGSL"),
+        ))?;
+    }
+    if name.starts_with("__") {
+        Err(syn::Error::new(
+            span,
+            "Names starting with `__` are reserved in WGSL",
+        ))?;
+    }
+    Ok(())
+}
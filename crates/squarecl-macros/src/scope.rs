@@ -1,15 +1,32 @@
-use std::collections::HashMap;
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote_spanned};
+use syn::{parse_quote, Ident, Lifetime, Type};
 
-use proc_macro2::TokenStream;
-use quote::quote_spanned;
-use syn::{spanned::Spanned, Ident, Type};
-
-use crate::expression::generate_var;
+use crate::{
+    expression::generate_var,
+    infer::{Term, TypeTable},
+};
 
 pub struct Context {
     scopes: Vec<Scope>,
     // Allows for global variable analysis
     scope_history: Vec<Scope>,
+    // Labels of the enclosing loops, innermost last
+    loops: Vec<Option<Lifetime>>,
+    // Name of the function, which can't call itself
+    function: Option<Ident>,
+    // Type parameters of the function
+    type_params: Vec<Ident>,
+    // Declared return type of the function
+    return_type: Type,
+    // Spans of the explicit `return`s
+    returns: Vec<Span>,
+    // Parameters known at expansion time
+    comptime: Vec<(Ident, Type)>,
+    // Number of temporaries created by the macro
+    temporaries: usize,
+    // Inferred types of the locals and unsuffixed literals
+    types: TypeTable,
 }
 
 impl Default for Context {
@@ -17,17 +34,28 @@ impl Default for Context {
         Self {
             scopes: vec![Scope::default()],
             scope_history: Default::default(),
+            loops: Default::default(),
+            function: None,
+            type_params: Default::default(),
+            return_type: parse_quote![()],
+            returns: Default::default(),
+            comptime: Default::default(),
+            temporaries: 0,
+            types: Default::default(),
         }
     }
 }
 
 impl Context {
-    pub fn push_variable(&mut self, name: Ident, ty: Option<Type>) {
+    /// Declare a variable in the current scope. Returns its slot in the type table.
+    pub fn push_variable(&mut self, name: Ident, ty: Option<Type>) -> usize {
+        let id = self.types.new_var(ty.clone());
         self.scopes
             .last_mut()
             .expect("Scopes must at least have root scope")
             .variables
-            .push((name, ty));
+            .push((name, ty, id));
+        id
     }
 
     pub fn push_scope(&mut self) {
@@ -39,11 +67,75 @@ impl Context {
         self.scope_history.push(scope);
     }
 
-    pub fn restore_scope(&mut self) {
-        let scope = self.scope_history.pop();
-        if let Some(scope) = scope {
-            self.scopes.push(scope);
+    pub fn push_loop(&mut self, label: Option<Lifetime>) {
+        self.loops.push(label)
+    }
+
+    pub fn pop_loop(&mut self) {
+        self.loops.pop().expect("Can't pop loop outside of a loop");
+    }
+
+    pub fn in_loop(&self) -> bool {
+        !self.loops.is_empty()
+    }
+
+    pub fn has_loop_label(&self, label: &Lifetime) -> bool {
+        self.loops
+            .iter()
+            .flatten()
+            .any(|loop_label| loop_label == label)
+    }
+
+    pub fn set_function(&mut self, name: Ident, type_params: Vec<Ident>) {
+        self.function = Some(name);
+        self.type_params = type_params;
+    }
+
+    /// Whether `name` is the function being expanded
+    pub fn is_function(&self, name: &Ident) -> bool {
+        self.function.as_ref() == Some(name)
+    }
+
+    pub fn is_type_param(&self, name: &Ident) -> bool {
+        self.type_params.contains(name)
+    }
+
+    pub fn set_return_type(&mut self, ty: Type) {
+        self.return_type = ty;
+    }
+
+    pub fn return_type(&self) -> &Type {
+        &self.return_type
+    }
+
+    pub fn push_return(&mut self, span: Span) {
+        self.returns.push(span);
+    }
+
+    pub fn returns(&self) -> &[Span] {
+        &self.returns
+    }
+
+    pub fn push_comptime(&mut self, name: Ident, ty: Type) {
+        self.comptime.push((name, ty));
+    }
+
+    /// Type of a comptime parameter, unless it's shadowed by a variable
+    pub fn comptime_type(&self, name: &Ident) -> Option<Type> {
+        if self.variable_type(name).is_some() {
+            return None;
         }
+        self.comptime
+            .iter()
+            .find(|(ident, _)| ident == name)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    /// New variable name for a temporary, like `_tuple_0`
+    pub fn new_temporary(&mut self, name: &str) -> Ident {
+        let ident = format_ident!("_{name}_{}", self.temporaries);
+        self.temporaries += 1;
+        ident
     }
 
     pub fn current_scope(&self) -> &Scope {
@@ -53,36 +145,55 @@ impl Context {
     }
 
     pub fn variable_type(&self, name: &Ident) -> Option<Option<Type>> {
+        self.variable_id(name).map(|id| self.types.resolve(id))
+    }
+
+    pub fn variable_id(&self, name: &Ident) -> Option<usize> {
         // Walk through each scope backwards until we find the variable.
         self.scopes
             .iter()
             .rev()
             .flat_map(|scope| scope.variables.iter().rev())
-            .find(|(ident, _)| name.to_string() == ident.to_string())
-            .map(|(_, ty)| ty.clone())
+            .find(|(ident, _, _)| ident == name)
+            .map(|(_, _, id)| *id)
+    }
+
+    pub fn resolve_type(&self, id: usize) -> Option<Type> {
+        self.types.resolve(id)
+    }
+
+    pub fn new_literal(&mut self, default: Type, span: Span) -> usize {
+        self.types.new_literal(default, span)
+    }
+
+    pub fn unify(&mut self, expected: Term, found: Term, span: Span) {
+        self.types.unify(expected, found, span)
+    }
+
+    pub fn types(&self) -> &TypeTable {
+        &self.types
     }
 
     pub fn extend(&mut self, vars: impl IntoIterator<Item = (Ident, Option<Type>)>) {
-        self.scopes
-            .last_mut()
-            .expect("Scopes must at least have root scope")
-            .variables
-            .extend(vars)
+        for (name, ty) in vars {
+            self.push_variable(name, ty);
+        }
     }
 }
 
 #[derive(Default)]
 pub struct Scope {
-    variables: Vec<(Ident, (Option<Type>))>,
+    // Declared type and slot in the type table of each variable
+    variables: Vec<(Ident, Option<Type>, usize)>,
 }
 
 impl Scope {
     pub fn generate_vars(&self) -> Vec<TokenStream> {
         self.variables
             .iter()
-            .map(|(ident, ty)| {
-                let mut span = ident.span();
-                let var = generate_var(ident, ty, span.clone());
+            .map(|(ident, ty, _)| {
+                let span = ident.span();
+                let var = generate_var(ident, ty, span);
                 quote_spanned! {span=>
                     let #ident = #var;
                 }
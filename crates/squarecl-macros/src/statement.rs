@@ -1,13 +1,24 @@
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned, ToTokens};
-use syn::{spanned::Spanned, Ident, Pat, Path, Stmt, Type};
+use syn::{
+    parse_quote, parse_quote_spanned, spanned::Spanned, visit::Visit, Block, Expr, Ident, Index,
+    Lifetime, Local, LocalInit, Pat, PatIdent, PatTuple, PatType, Stmt, Type,
+};
 
 use crate::{
+    branch::{
+        expand_break, expand_continue, expand_for_loop, expand_loop, expand_return,
+        expand_while_loop,
+    },
     expression::{generate_var, Expression},
+    infer::Term,
+    intrinsic::expand_init,
     ir_type, prefix_ir,
+    reserved::check_name,
     scope::Context,
 };
 
+#[allow(clippy::large_enum_variant)]
 pub enum Statement {
     Local {
         left: Box<Expression>,
@@ -21,6 +32,43 @@ pub enum Statement {
         terminated: bool,
         span: Span,
     },
+    ForLoop {
+        label: Option<Lifetime>,
+        variable: Ident,
+        /// Slot of the loop variable in the type table
+        id: usize,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        step: Option<Box<Expression>>,
+        inclusive: bool,
+        block: Vec<Statement>,
+        ty: Option<Type>,
+        span: Span,
+    },
+    WhileLoop {
+        label: Option<Lifetime>,
+        condition: Box<Expression>,
+        block: Vec<Statement>,
+        span: Span,
+    },
+    Loop {
+        label: Option<Lifetime>,
+        block: Vec<Statement>,
+        span: Span,
+    },
+    Return {
+        value: Option<Box<Expression>>,
+        ty: Type,
+        span: Span,
+    },
+    Break {
+        label: Option<Lifetime>,
+        span: Span,
+    },
+    Continue {
+        label: Option<Lifetime>,
+        span: Span,
+    },
 }
 
 impl Statement {
@@ -31,18 +79,20 @@ impl Statement {
                 let (ident, ty, mutable) = local_pat(local.pat)?;
                 let init = local
                     .init
-                    .map(|init| Expression::from_expr(*init.expr, context))
+                    .map(|init| expand_init(*init.expr, context))
                     .transpose()?
                     .map(Box::new);
-                let init_ty = init.as_ref().and_then(|init| init.ty());
-
+                let id = context.push_variable(ident.clone(), ty.clone());
+                if let Some(init) = &init {
+                    context.unify(Term::Var(id), init.term(), init.span());
+                }
                 let variable = Box::new(Expression::Variable {
-                    name: ident.clone(),
-                    span: span.clone(),
+                    name: ident,
+                    span,
                     ty: ty.clone(),
+                    id,
                 });
 
-                context.push_variable(ident, ty.clone());
                 Self::Local {
                     left: variable,
                     init,
@@ -51,20 +101,237 @@ impl Statement {
                     span,
                 }
             }
-            Stmt::Expr(expr, semi) => Statement::Expression {
-                terminated: semi.is_some(),
-                span: expr.span(),
-                expression: Box::new(Expression::from_expr(expr, context)?),
-            },
+            Stmt::Expr(Expr::ForLoop(for_loop), _) => expand_for_loop(for_loop, context)?,
+            Stmt::Expr(Expr::While(while_loop), _) => expand_while_loop(while_loop, context)?,
+            Stmt::Expr(Expr::Loop(expr_loop), _) => expand_loop(expr_loop, context)?,
+            Stmt::Expr(Expr::Return(expr_return), _) => expand_return(expr_return, context)?,
+            Stmt::Expr(Expr::Break(expr_break), _) => expand_break(expr_break, context)?,
+            Stmt::Expr(Expr::Continue(expr_continue), _) => {
+                expand_continue(expr_continue, context)?
+            }
+            Stmt::Expr(expr, semi) => {
+                let span = expr.span();
+                let expression = Expression::from_expr(expr, context)?;
+                Statement::Expression {
+                    terminated: semi.is_some() || expression.is_statement(),
+                    span,
+                    expression: Box::new(expression),
+                }
+            }
             stmt => Err(syn::Error::new_spanned(stmt, "Unsupported statement"))?,
         };
         Ok(statement)
     }
 }
 
+/// Parse the statements of a block in a new scope
+pub fn parse_block(block: Block, context: &mut Context) -> syn::Result<Vec<Statement>> {
+    context.push_scope();
+    let mut statements = Vec::new();
+    for statement in block.stmts {
+        match statement {
+            Stmt::Local(local) if is_destructuring(&local.pat) => {
+                statements.extend(expand_destructuring(local, context)?)
+            }
+            statement => statements.push(Statement::from_stmt(statement, context)?),
+        }
+    }
+    context.pop_scope();
+    Ok(statements)
+}
+
+fn is_destructuring(pat: &Pat) -> bool {
+    match pat {
+        Pat::Tuple(_) | Pat::Wild(_) | Pat::Paren(_) => true,
+        Pat::Type(pat) => is_destructuring(&pat.pat),
+        _ => false,
+    }
+}
+
+/// Desugar `let (a, (b, _)) = init;` into a local for each name. Values that aren't tuple literals
+/// are bound to a temporary first, so they're only evaluated once.
+fn expand_destructuring(local: Local, context: &mut Context) -> syn::Result<Vec<Statement>> {
+    let span = local.span();
+    let init = match local.init {
+        Some(LocalInit {
+            diverge: Some((_, diverge)),
+            ..
+        }) => Err(syn::Error::new_spanned(
+            diverge,
+            "`let else` is not supported",
+        ))?,
+        Some(init) => *init.expr,
+        None => Err(syn::Error::new(
+            span,
+            "Destructuring `let` must have an initializer",
+        ))?,
+    };
+    let mut locals = Vec::new();
+    destructure(local.pat, init, context, &mut locals)?;
+
+    let mut statements = Vec::new();
+    for (pat, init) in locals {
+        let stmt = match pat {
+            // Calls can have side effects, other discarded values are dropped
+            Pat::Wild(_) if matches!(init, Expr::Call(_)) => {
+                Stmt::Expr(init, Some(parse_quote![;]))
+            }
+            Pat::Wild(_) => continue,
+            pat => parse_quote_spanned! {span=> let #pat = #init; },
+        };
+        statements.push(Statement::from_stmt(stmt, context)?);
+    }
+    Ok(statements)
+}
+
+/// Split a pattern into simple `let`s, pushed to `locals` in evaluation order
+fn destructure(
+    pat: Pat,
+    init: Expr,
+    context: &mut Context,
+    locals: &mut Vec<(Pat, Expr)>,
+) -> syn::Result<()> {
+    match pat {
+        Pat::Paren(pat) => destructure(*pat.pat, init, context, locals)?,
+        Pat::Type(PatType { pat, ty, .. }) if is_destructuring(&pat) => match (*pat, *ty, init) {
+            // Distribute the element types over the elements
+            (Pat::Tuple(pat), Type::Tuple(ty), Expr::Tuple(init))
+                if pat.elems.len() == ty.elems.len() =>
+            {
+                let elems = pat.elems.into_iter().zip(ty.elems).map(|(pat, ty)| {
+                    Pat::Type(PatType {
+                        attrs: Vec::new(),
+                        pat: Box::new(pat),
+                        colon_token: Default::default(),
+                        ty: Box::new(ty),
+                    })
+                });
+                let pat = Pat::Tuple(PatTuple {
+                    attrs: pat.attrs,
+                    paren_token: pat.paren_token,
+                    elems: elems.collect(),
+                });
+                destructure(pat, Expr::Tuple(init), context, locals)?;
+            }
+            (Pat::Wild(pat), _, init) => locals.push((Pat::Wild(pat), init)),
+            (pat, ty, init) => {
+                let temporary = context.new_temporary("tuple");
+                locals.push((parse_quote![#temporary: #ty], init));
+                destructure(pat, parse_quote![#temporary], context, locals)?;
+            }
+        },
+        Pat::Tuple(pat) => {
+            if let Some(rest) = pat.elems.iter().find(|pat| matches!(pat, Pat::Rest(_))) {
+                Err(syn::Error::new_spanned(
+                    rest,
+                    "Rest patterns are not supported",
+                ))?;
+            }
+            let names = bound_names(&Pat::Tuple(pat.clone()));
+            match init {
+                Expr::Tuple(init) if init.elems.len() != pat.elems.len() => {
+                    Err(syn::Error::new_spanned(
+                        &init,
+                        format!("Expected a tuple of {} elements", pat.elems.len()),
+                    ))?;
+                }
+                Expr::Tuple(init) => {
+                    // An element can't see the names bound by previous elements
+                    let elems = if references_any(init.elems.iter(), &names) {
+                        init.elems
+                            .into_iter()
+                            .map(|elem| {
+                                let temporary = context.new_temporary("tuple");
+                                locals.push((parse_quote![#temporary], elem));
+                                parse_quote![#temporary]
+                            })
+                            .collect::<Vec<Expr>>()
+                    } else {
+                        init.elems.into_iter().collect()
+                    };
+                    for (pat, elem) in pat.elems.into_iter().zip(elems) {
+                        destructure(pat, elem, context, locals)?;
+                    }
+                }
+                init => {
+                    // Paths and fields are cheap to access again, unless they're shadowed
+                    let reusable = matches!(init, Expr::Path(_) | Expr::Field(_))
+                        && !references_any([&init], &names);
+                    let base = if reusable {
+                        init
+                    } else {
+                        let temporary = context.new_temporary("tuple");
+                        locals.push((parse_quote![#temporary], init));
+                        parse_quote![#temporary]
+                    };
+                    for (index, pat) in pat.elems.into_iter().enumerate() {
+                        let index = Index::from(index);
+                        destructure(pat, parse_quote![#base.#index], context, locals)?;
+                    }
+                }
+            }
+        }
+        pat => locals.push((pat, init)),
+    }
+    Ok(())
+}
+
+/// Names bound by a pattern
+fn bound_names(pat: &Pat) -> Vec<Ident> {
+    struct Names(Vec<Ident>);
+    impl<'a> Visit<'a> for Names {
+        fn visit_pat_ident(&mut self, pat: &'a PatIdent) {
+            self.0.push(pat.ident.clone());
+        }
+    }
+    let mut names = Names(Vec::new());
+    names.visit_pat(pat);
+    names.0
+}
+
+/// Whether any of the expressions uses one of the names
+fn references_any<'a>(exprs: impl IntoIterator<Item = &'a Expr>, names: &[Ident]) -> bool {
+    struct References<'b>(&'b [Ident], bool);
+    impl<'a, 'b> Visit<'a> for References<'b> {
+        fn visit_path(&mut self, path: &'a syn::Path) {
+            self.1 |= path.get_ident().is_some_and(|ident| self.0.contains(ident));
+        }
+    }
+    let mut references = References(names, false);
+    for expr in exprs {
+        references.visit_expr(expr);
+    }
+    references.1
+}
+
+/// Generate an expression that evaluates to the `Vec` of IR statements of a block
+pub fn generate_block(statements: &[Statement]) -> TokenStream {
+    quote! {
+        {
+            let mut __statements = Vec::new();
+            #(#statements)*
+            __statements
+        }
+    }
+}
+
+/// Loop labels are stored without the leading `'`
+fn generate_label(label: &Option<Lifetime>) -> TokenStream {
+    match label {
+        Some(label) => {
+            let name = label.ident.to_string();
+            quote![Some(#name.to_string())]
+        }
+        None => quote![None],
+    }
+}
+
 fn local_pat(pat: Pat) -> syn::Result<(Ident, Option<Type>, bool)> {
     let res = match pat {
-        Pat::Ident(ident) => (ident.ident, None, ident.mutability.is_some()),
+        Pat::Ident(ident) => {
+            check_name(&ident.ident.to_string(), ident.ident.span())?;
+            (ident.ident, None, ident.mutability.is_some())
+        }
         Pat::Type(pat) => {
             let ty = *pat.ty;
             let (ident, _, mutable) = local_pat(*pat.pat)?;
@@ -91,21 +358,16 @@ impl ToTokens for Statement {
                 span,
                 ty,
             } => {
-                let span = span.clone();
+                let span = *span;
 
                 let name = match &**left {
                     Expression::Variable { name, .. } => name,
-                    Expression::Init { left, .. } => match &**left {
-                        Expression::Variable { name, .. } => name,
-                        _ => panic!("Init left is always variable"),
-                    },
-                    _ => panic!("Local is always variable or init"),
+                    _ => panic!("Local is always variable"),
                 };
                 // Separate init and declaration in case initializer uses an identically named
                 // variable that would be overwritten by the declaration.
                 let initializer = init.as_ref().map(|init| quote![let __init = #init;]);
-                let left = if let Some(init) = init {
-                    let span = span.clone();
+                let left = if init.is_some() {
                     let init_ty = ir_type("Initializer");
                     quote_spanned! {span=>
                         #init_ty {
@@ -114,7 +376,7 @@ impl ToTokens for Statement {
                         }
                     }
                 } else {
-                    quote![Box::new(#name)]
+                    quote![#name]
                 };
                 let variable = generate_var(name, ty, span);
                 let variable_decl = quote_spanned! {span=>
@@ -143,24 +405,114 @@ impl ToTokens for Statement {
                     });
                 }
             }
+            // Trailing values of functions and value blocks are taken out while parsing, so any
+            // expression left over only runs for its side effects
             Statement::Expression {
-                expression,
-                terminated,
+                expression, span, ..
+            } => {
+                let span = *span;
+                quote_spanned! {span=>
+                    __statements.push(#statement::Expression {
+                        expression: Box::new(#expr::expression_untyped(&#expression))
+                    });
+                }
+            }
+            Statement::ForLoop {
+                label,
+                variable,
+                start,
+                end,
+                step,
+                inclusive,
+                block,
+                ty,
                 span,
+                ..
             } => {
-                let span = span.clone();
-                if *terminated {
-                    quote_spanned! {span=>
-                        __statements.push(#statement::Expression {
-                            expression: Box::new(#expr::expression_untyped(&#expression))
-                        });
-                    }
-                } else {
-                    quote_spanned! {span=>
-                        __statements.push(#statement::ImplicitReturn {
-                            expression: Box::new(#expr::expression_untyped(&#expression))
-                        });
-                    }
+                let span = *span;
+                let for_ty = prefix_ir(format_ident!("ForLoop"));
+                let variable_decl = generate_var(variable, ty, span);
+                let step = match step {
+                    Some(step) => quote![Some(Box::new(#step))],
+                    None => quote![None],
+                };
+                let step_ty = quote![Option<Box<dyn #expr<Output = _>>>];
+                let label = generate_label(label);
+                let block = generate_block(block);
+                quote_spanned! {span=>
+                    __statements.push({
+                        let __start = #start;
+                        let __end = #end;
+                        let __step: #step_ty = #step;
+                        let #variable = #variable_decl;
+                        #for_ty {
+                            label: #label,
+                            variable: #variable,
+                            start: Box::new(__start),
+                            end: Box::new(__end),
+                            step: __step,
+                            inclusive: #inclusive,
+                            block: #block,
+                        }
+                        .into()
+                    });
+                }
+            }
+            Statement::WhileLoop {
+                label,
+                condition,
+                block,
+                span,
+            } => {
+                let span = *span;
+                let while_ty = prefix_ir(format_ident!("WhileLoop"));
+                let label = generate_label(label);
+                let block = generate_block(block);
+                quote_spanned! {span=>
+                    __statements.push(#while_ty {
+                        label: #label,
+                        condition: Box::new(#condition),
+                        block: #block,
+                    }.into());
+                }
+            }
+            Statement::Loop { label, block, span } => {
+                let span = *span;
+                let label = generate_label(label);
+                let block = generate_block(block);
+                quote_spanned! {span=>
+                    __statements.push(#statement::Loop {
+                        label: #label,
+                        block: #block,
+                    });
+                }
+            }
+            Statement::Return { value, ty, span } => {
+                let span = *span;
+                let value = match value {
+                    Some(value) => quote_spanned! {span=> {
+                        // Check the value against the declared return type
+                        let __value: Box<dyn #expr<Output = #ty>> = Box::new(#value);
+                        Some(Box::new(#expr::expression_untyped(&*__value)))
+                    }},
+                    None => quote![None],
+                };
+                quote_spanned! {span=>
+                    __statements.push(#statement::Return { value: #value });
+                }
+            }
+            Statement::Break { label, span } => {
+                let span = *span;
+                let label = generate_label(label);
+                quote_spanned! {span=>
+                    __statements.push(#statement::Break { label: #label });
+                }
+            }
+            Statement::Continue { label, span } => {
+                let span = *span;
+                let label = generate_label(label);
+                quote_spanned! {span=>
+                    __statements.push(#statement::Continue { label: #label });
                 }
             }
         };
@@ -0,0 +1,19 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+fn by_value(input: Array<f32>, out: &mut Array<f32>) {
+    out[0] = input[0];
+}
+
+#[square]
+fn in_tuple(input: (Array<f32>, u32), out: &mut Array<f32>) {
+    out[0] = 1.0;
+}
+
+#[square]
+fn bools(mask: &Array<bool>, out: &mut Array<u32>) {
+    out[0] = 1;
+}
+
+fn main() {}
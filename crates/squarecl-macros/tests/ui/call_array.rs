@@ -0,0 +1,14 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+fn fill(out: &mut Array<u32>, value: u32) {
+    out[0] = value;
+}
+
+#[square]
+fn caller(out: &mut Array<u32>) {
+    fill(out, 1);
+}
+
+fn main() {}
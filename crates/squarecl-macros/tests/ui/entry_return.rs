@@ -0,0 +1,8 @@
+use squarecl_macros::square;
+
+#[square(workgroup_size = (64, 1, 1))]
+fn first(input: u32) -> u32 {
+    input
+}
+
+fn main() {}
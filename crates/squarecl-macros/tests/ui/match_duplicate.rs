@@ -0,0 +1,12 @@
+use squarecl_macros::square;
+
+#[square]
+fn modes(a: u32) {
+    match a {
+        1 | 2 => {}
+        0x2 => {}
+        _ => {}
+    }
+}
+
+fn main() {}
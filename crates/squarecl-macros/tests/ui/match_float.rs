@@ -0,0 +1,17 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+fn half(x: f32) -> f32 {
+    x / 2.0
+}
+
+#[square]
+fn modes(a: f32, out: &mut Array<u32>) {
+    match half(a) {
+        1 => out[0] = 1,
+        _ => {}
+    }
+}
+
+fn main() {}
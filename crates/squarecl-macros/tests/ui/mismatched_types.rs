@@ -0,0 +1,19 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+fn mixed(a: u32, b: f32, out: &mut Array<u32>) {
+    let mut x = 0;
+    x = a;
+    x = b;
+    out[0] = x;
+}
+
+#[square]
+fn literals(out: &mut Array<f32>) {
+    let mut x = 0;
+    x = 1.5;
+    out[0] = x;
+}
+
+fn main() {}
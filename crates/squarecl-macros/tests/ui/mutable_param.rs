@@ -0,0 +1,10 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+fn increment(mut a: u32, out: &mut Array<u32>) {
+    a += 1;
+    out[0] = a;
+}
+
+fn main() {}
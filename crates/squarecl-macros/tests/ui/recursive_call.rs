@@ -0,0 +1,12 @@
+use squarecl_macros::square;
+
+#[square]
+fn factorial(n: u32) -> u32 {
+    if n > 1 {
+        n * factorial(n - 1)
+    } else {
+        1
+    }
+}
+
+fn main() {}
@@ -0,0 +1,42 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::{square, SquareType};
+
+#[square]
+fn switch(out: &mut Array<u32>) {
+    out[0] = 1;
+}
+
+#[square]
+fn convert(out: &mut Array<u32>, cast: u32) {
+    out[0] = cast;
+}
+
+#[square]
+fn count(out: &mut Array<u32>) {
+    let filter = 1;
+    out[0] = filter;
+}
+
+#[square]
+fn repeat(out: &mut Array<u32>) {
+    for __index in 0..4 {
+        out[0] += 1;
+    }
+}
+
+#[square(entry = "sampler")]
+fn sample(out: &mut Array<u32>) {
+    out[0] = 1;
+}
+
+#[derive(SquareType)]
+struct CompileShader {
+    x: u32,
+}
+
+#[derive(SquareType)]
+struct Filter {
+    sampler: u32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use squarecl_core::ir::{Array, SharedMemory};
+use squarecl_macros::square;
+
+#[square]
+fn reduce(out: &mut Array<f32>) {
+    let mut tile = SharedMemory::<f32>::new(4);
+    tile = SharedMemory::<f32>::new(8);
+    out[0] = tile[0];
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square(workgroup_size = (64, 0, 1))]
+fn empty(out: &mut Array<u32>) {
+    out[0] = 1;
+}
+
+fn main() {}
@@ -2,24 +2,24 @@ use std::fmt::{Display, Error, Formatter};
 
 use derive_more::derive::Deref;
 use squarecl_core::{
-    ir::{Expression, IRType, Operator, Statement},
+    ir::{Expression, Function, IRType, Operator, Statement},
     new_local_var,
 };
 
-pub struct WgpuKernel(pub Vec<Statement>);
+pub struct WgpuKernel(pub Function);
 #[derive(Deref)]
-struct WgpuStatement<'a>(&'a Statement);
+struct WgpuStatement<'a>(#[deref] &'a Statement, JumpScope<'a>);
 
 #[derive(Deref)]
-struct WgpuExpression<'a>(&'a Box<Expression>);
+struct WgpuExpression<'a>(#[deref] &'a Expression, JumpScope<'a>);
 
 #[derive(Deref)]
 struct WgpuOperator<'a>(&'a Operator);
 
 struct WgpuType(IRType);
 
-fn e(expr: &Box<Expression>) -> WgpuExpression<'_> {
-    WgpuExpression(expr)
+fn e<'a>(expr: &'a Expression, scope: JumpScope<'a>) -> WgpuExpression<'a> {
+    WgpuExpression(expr, scope)
 }
 
 fn o(expr: &Operator) -> WgpuOperator<'_> {
@@ -28,9 +28,30 @@ fn o(expr: &Operator) -> WgpuOperator<'_> {
 
 impl Display for WgpuKernel {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "fn main(a: u32, b: u32) {{")?;
-        for statement in &self.0 {
-            let statement = WgpuStatement(statement);
+        let kernel = &self.0;
+        for (index, binding) in kernel.bindings.iter().enumerate() {
+            let access = if binding.mutable {
+                "read_write"
+            } else {
+                "read"
+            };
+            let ty = WgpuType(binding.ty);
+            writeln!(
+                f,
+                "@group(0) @binding({index}) var<storage, {access}> {}: array<{ty}>;",
+                binding.name
+            )?;
+        }
+        for (index, (name, ty)) in kernel.params.iter().enumerate() {
+            let index = kernel.bindings.len() + index;
+            let ty = WgpuType(*ty);
+            writeln!(f, "@group(0) @binding({index}) var<uniform> {name}: {ty};")?;
+        }
+        let [x, y, z] = kernel.workgroup_size;
+        writeln!(f, "@compute @workgroup_size({x}, {y}, {z})")?;
+        writeln!(f, "fn {}() {{", kernel.entry_name())?;
+        for statement in &self.0.body {
+            let statement = WgpuStatement(statement, JumpScope::default());
             write!(f, "{statement}")?;
         }
         writeln!(f, "}}")
@@ -45,30 +66,111 @@ impl<'a> Display for WgpuStatement<'a> {
                     let ty = WgpuType(*ty);
                     writeln!(f, "var {name}: {ty};")
                 }
+                Expression::Init { left, right, .. }
+                    if matches!(**right, Expression::SharedMemory { .. }) =>
+                {
+                    let Expression::SharedMemory { size, ty } = &**right else {
+                        unreachable!()
+                    };
+                    let ty = WgpuType(*ty);
+                    writeln!(
+                        f,
+                        "var<workgroup> {}: array<{ty}, {size}>;",
+                        e(left, self.1)
+                    )
+                }
                 Expression::Init { left, right, ty } => {
-                    let variable = e(left);
+                    let variable = e(left, self.1);
                     let ty = WgpuType(*ty);
-                    let init = e(right);
+                    let init = e(right, self.1);
                     writeln!(f, "var {variable}: {ty};")?;
                     write!(f, "{init}")
                 }
                 _ => panic!("Local declaration must be init or variable"),
             },
             Statement::Expression { expression } => {
-                let expression = e(expression);
+                let expression = e(expression, self.1);
                 writeln!(f, "{expression};")
             }
             Statement::ImplicitReturn { expression } => {
-                let expression = e(expression);
+                let expression = e(expression, self.1);
                 writeln!(f, "return {expression};")
             }
+            Statement::ForLoop {
+                label,
+                variable,
+                start,
+                end,
+                step,
+                inclusive,
+                block,
+            } => {
+                let ty = WgpuType(variable.ir_type());
+                let variable = e(variable, self.1);
+                let start = e(start, self.1);
+                let end = e(end, self.1);
+                let comparison = if *inclusive { "<=" } else { "<" };
+                let header = format!(
+                    "for (var {variable}: {ty} = {start}; {variable} {comparison} {end}; ) {{"
+                );
+                let increment = match step {
+                    Some(step) => format!("{variable} = add({variable}, {});", e(step, self.1)),
+                    None => format!("{variable} = add({variable}, 1);"),
+                };
+                let loop_ = Loop {
+                    label: label.as_deref(),
+                    header: &header,
+                    condition: None,
+                    increment: Some(&increment),
+                    block,
+                };
+                loop_.write(f, self.1)
+            }
+            Statement::WhileLoop {
+                label,
+                condition,
+                block,
+            } => {
+                let condition = format!("if not({}) {{ break; }}", e(condition, self.1));
+                let loop_ = Loop {
+                    label: label.as_deref(),
+                    header: "loop {",
+                    condition: Some(&condition),
+                    increment: None,
+                    block,
+                };
+                loop_.write(f, self.1)
+            }
+            Statement::Loop { label, block } => {
+                let loop_ = Loop {
+                    label: label.as_deref(),
+                    header: "loop {",
+                    condition: None,
+                    increment: None,
+                    block,
+                };
+                loop_.write(f, self.1)
+            }
+            Statement::Return { value: Some(value) } => {
+                let value = e(value, self.1);
+                writeln!(f, "return {value};")
+            }
+            Statement::Return { value: None } => writeln!(f, "return;"),
+            Statement::Break { label } => {
+                let jump = self.1.jump(JumpKind::Break, label.as_deref());
+                self.1.write_jump(f, jump)
+            }
+            Statement::Continue { label } => {
+                let jump = self.1.jump(JumpKind::Continue, label.as_deref());
+                self.1.write_jump(f, jump)
+            }
         }
     }
 }
 
 impl<'a> Display for WgpuExpression<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match &**self.0 {
+        match self.0 {
             Expression::Binary {
                 left,
                 operator,
@@ -76,31 +178,155 @@ impl<'a> Display for WgpuExpression<'a> {
                 ..
             } => {
                 let out = new_local_var();
-                let left = e(left);
+                let left = e(left, self.1);
                 let operator = o(operator);
-                let right = e(right);
+                let right = e(right, self.1);
                 writeln!(f, "{out} = {operator}({left}, {right});",)
             }
             Expression::Unary {
                 input, operator, ..
             } => {
                 let out = new_local_var();
-                let input = e(input);
+                let input = e(input, self.1);
                 let operator = o(operator);
                 writeln!(f, "{out} = {operator}({input});")
             }
             Expression::Variable { name, .. } => write!(f, "{name}"),
             Expression::Literal { value, ty } => format_lit(f, value, ty), // TODO: Types
             Expression::Assigment { left, right, .. } => {
-                let left = e(left);
-                let right = e(right);
+                let left = e(left, self.1);
+                let right = e(right, self.1);
                 writeln!(f, "{left} = {right};")
             }
+            Expression::CompoundAssignment {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = e(left, self.1);
+                let operator = o(operator);
+                let right = e(right, self.1);
+                writeln!(f, "{left} = {operator}({left}, {right});")
+            }
+            Expression::Cast { input, to, .. } => {
+                let out = new_local_var();
+                let input = e(input, self.1);
+                let to = WgpuType(*to);
+                writeln!(f, "{out} = {to}({input});")
+            }
+            Expression::Intrinsic {
+                intrinsic, args, ..
+            } => {
+                let out = new_local_var();
+                let args = args
+                    .iter()
+                    .map(|arg| e(arg, self.1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(f, "{out} = {intrinsic:?}({args});")
+            }
+            Expression::Index { array, index, .. } => {
+                let array = e(array, self.1);
+                let index = e(index, self.1);
+                write!(f, "{array}[{index}]")
+            }
+            Expression::Call { function, args, .. } => {
+                let out = new_local_var();
+                let args = args
+                    .iter()
+                    .map(|arg| e(arg, self.1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(f, "{out} = {}({args});", function.name)
+            }
+            Expression::Tuple { elements, ty } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| e(element, self.1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{}({elements})", WgpuType(*ty))
+            }
+            Expression::Field { base, name, .. } => {
+                let base = e(base, self.1);
+                write!(f, "{base}.{name}")
+            }
+            Expression::SharedMemory { .. } => panic!("Shared memory must be assigned to a local"),
             Expression::Init { left, right, .. } => {
-                let left = e(left);
-                let right = e(right);
+                let left = e(left, self.1);
+                let right = e(right, self.1);
                 writeln!(f, "{left} = {right}")
             }
+            Expression::Block { block, .. } => {
+                writeln!(f, "{{")?;
+                for statement in block {
+                    write!(f, "{}", WgpuStatement(statement, self.1))?;
+                }
+                writeln!(f, "}}")
+            }
+            Expression::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                let condition = e(condition, self.1);
+                writeln!(f, "if {condition} {{")?;
+                for statement in then_block {
+                    write!(f, "{}", WgpuStatement(statement, self.1))?;
+                }
+                if let Some(else_block) = else_block {
+                    writeln!(f, "}} else {{")?;
+                    for statement in else_block {
+                        write!(f, "{}", WgpuStatement(statement, self.1))?;
+                    }
+                }
+                writeln!(f, "}}")
+            }
+            Expression::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let scope = self.1;
+                let inner = JumpScope {
+                    switch_depth: scope.switch_depth + 1,
+                    ..scope
+                };
+                let value = e(value, scope);
+                writeln!(f, "switch {value} {{")?;
+                for (values, block) in cases {
+                    let values = values
+                        .iter()
+                        .map(|value| e(value, scope).to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(f, "case {values}: {{")?;
+                    for statement in block {
+                        write!(f, "{}", WgpuStatement(statement, inner))?;
+                    }
+                    writeln!(f, "}}")?;
+                }
+                writeln!(f, "default: {{")?;
+                for statement in default {
+                    write!(f, "{}", WgpuStatement(statement, inner))?;
+                }
+                writeln!(f, "}}")?;
+                writeln!(f, "}}")?;
+
+                let mut jumps = Vec::new();
+                let mut loops = scope.loops.to_vec();
+                for block in cases
+                    .iter()
+                    .map(|(_, block)| block.as_slice())
+                    .chain([default.as_slice()])
+                {
+                    collect_jumps(block, &mut loops, inner.switch_depth, &mut jumps);
+                }
+                // `break` only leaves the `switch`, so every jump to a loop has to be propagated
+                jumps.retain(|jump| jump.depth < scope.loops.len());
+                scope.write_jump_checks(f, &jumps)
+            }
         }
     }
 }
@@ -112,6 +338,20 @@ impl<'a> Display for WgpuOperator<'a> {
             Operator::Sub => write!(f, "sub"),
             Operator::Mul => write!(f, "mul"),
             Operator::Div => write!(f, "div"),
+            Operator::Rem => write!(f, "rem"),
+            Operator::BitAnd => write!(f, "bitwise_and"),
+            Operator::BitOr => write!(f, "bitwise_or"),
+            Operator::BitXor => write!(f, "bitwise_xor"),
+            Operator::Shl => write!(f, "shift_left"),
+            Operator::Shr => write!(f, "shift_right"),
+            Operator::Eq => write!(f, "equal"),
+            Operator::Ne => write!(f, "not_equal"),
+            Operator::Lt => write!(f, "lower"),
+            Operator::Le => write!(f, "lower_equal"),
+            Operator::Gt => write!(f, "greater"),
+            Operator::Ge => write!(f, "greater_equal"),
+            Operator::And => write!(f, "and"),
+            Operator::Or => write!(f, "or"),
             Operator::Deref => write!(f, "deref"),
             Operator::Not => write!(f, "not"),
             Operator::Neg => write!(f, "neg"),
@@ -125,6 +365,8 @@ impl Display for WgpuType {
             IRType::Int(32) => "i32",
             IRType::UInt(32) => "u32",
             IRType::Float(16) => "f32",
+            IRType::Bool => "bool",
+            IRType::Struct(ty) => ty.name,
             t => panic!("Unsupported data type {:?}", t),
         };
         write!(f, "{ty}")
@@ -136,7 +378,224 @@ fn format_lit(f: &mut Formatter<'_>, value: &str, ty: &IRType) -> Result<(), Err
         IRType::Int(32) => "i",
         IRType::UInt(32) => "u",
         IRType::Float(32) => "f",
+        IRType::Bool => "",
         t => panic!("Unsupported data type {:?}", t),
     };
     write!(f, "{value}{suffix}")
 }
+
+/// Loop statement, lowered to a WGSL loop with `header`. The `condition` is checked at the start of
+/// each iteration and the `increment` is run at the end.
+struct Loop<'a> {
+    label: Option<&'a str>,
+    header: &'a str,
+    condition: Option<&'a str>,
+    increment: Option<&'a str>,
+    block: &'a [Statement],
+}
+
+impl<'a> Loop<'a> {
+    fn write(&self, f: &mut Formatter<'_>, scope: JumpScope<'_>) -> std::fmt::Result {
+        let depth = scope.loops.len();
+        let mut loops = scope.loops.to_vec();
+        loops.push(self.label);
+        let inner = JumpScope {
+            loops: &loops,
+            switch_depth: 0,
+        };
+
+        let mut jumps = Vec::new();
+        collect_jumps(self.block, &mut loops.clone(), 0, &mut jumps);
+        let flag = |kind| {
+            let jump = Jump { kind, depth };
+            jumps.contains(&jump).then_some(jump)
+        };
+        let break_flag = flag(JumpKind::Break);
+        let continue_flag = flag(JumpKind::Continue);
+
+        if let Some(flag) = break_flag {
+            // Scope the flag so sibling loops don't collide
+            writeln!(f, "{{")?;
+            writeln!(f, "var {flag} = false;")?;
+        }
+        writeln!(f, "{}", self.header)?;
+        if let Some(condition) = self.condition {
+            writeln!(f, "{condition}")?;
+        }
+        if let Some(flag) = continue_flag {
+            writeln!(f, "var {flag} = false;")?;
+        }
+        for statement in self.block {
+            write!(f, "{}", WgpuStatement(statement, inner))?;
+        }
+        if let Some(increment) = self.increment {
+            writeln!(f, "{increment}")?;
+        }
+        writeln!(f, "}}")?;
+        if break_flag.is_some() {
+            writeln!(f, "}}")?;
+        }
+
+        // Propagate jumps that target a loop further out
+        jumps.retain(|jump| jump.depth < depth);
+        scope.write_jump_checks(f, &jumps)
+    }
+}
+
+/// Loops enclosing a statement, innermost last, and the number of `switch`es between the
+/// statement and the innermost loop. Labelled jumps and jumps out of a `switch` are lowered to a
+/// flag for the targeted loop, like in the wgpu backend.
+#[derive(Clone, Copy, Default)]
+struct JumpScope<'a> {
+    loops: &'a [Option<&'a str>],
+    switch_depth: usize,
+}
+
+impl<'a> JumpScope<'a> {
+    fn jump(&self, kind: JumpKind, label: Option<&str>) -> Jump {
+        let depth = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|loop_label| *loop_label == Some(label))
+                .expect("Labels are validated by the macro"),
+            None => self.loops.len() - 1,
+        };
+        Jump { kind, depth }
+    }
+
+    /// Whether a jump can be expressed as a plain `break` or `continue`
+    fn is_direct(&self, jump: Jump) -> bool {
+        let innermost = jump.depth + 1 == self.loops.len();
+        match jump.kind {
+            JumpKind::Break => innermost && self.switch_depth == 0,
+            JumpKind::Continue => innermost,
+        }
+    }
+
+    fn write_jump(&self, f: &mut Formatter<'_>, jump: Jump) -> std::fmt::Result {
+        if self.is_direct(jump) {
+            writeln!(f, "{};", jump.kind)
+        } else {
+            writeln!(f, "{jump} = true;")?;
+            writeln!(f, "break;")
+        }
+    }
+
+    /// Continue jumps whose flag was set inside a nested construct
+    fn write_jump_checks(&self, f: &mut Formatter<'_>, jumps: &[Jump]) -> std::fmt::Result {
+        for &jump in jumps {
+            let kind = match self.is_direct(jump) {
+                true => jump.kind,
+                false => JumpKind::Break,
+            };
+            writeln!(f, "if {jump} {{ {kind}; }}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JumpKind {
+    Break,
+    Continue,
+}
+
+impl Display for JumpKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpKind::Break => write!(f, "break"),
+            JumpKind::Continue => write!(f, "continue"),
+        }
+    }
+}
+
+/// A jump to the loop at `depth`, displayed as the name of its flag
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Jump {
+    kind: JumpKind,
+    depth: usize,
+}
+
+impl Display for Jump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "_{}_{}", self.kind, self.depth)
+    }
+}
+
+/// Collect the jumps in `block` that need a flag
+fn collect_jumps<'a>(
+    block: &'a [Statement],
+    loops: &mut Vec<Option<&'a str>>,
+    switch_depth: usize,
+    jumps: &mut Vec<Jump>,
+) {
+    for statement in block {
+        match statement {
+            Statement::Break { label } | Statement::Continue { label } => {
+                let kind = match statement {
+                    Statement::Break { .. } => JumpKind::Break,
+                    _ => JumpKind::Continue,
+                };
+                let scope = JumpScope {
+                    loops,
+                    switch_depth,
+                };
+                let jump = scope.jump(kind, label.as_deref());
+                if !scope.is_direct(jump) && !jumps.contains(&jump) {
+                    jumps.push(jump);
+                }
+            }
+            Statement::ForLoop { label, block, .. }
+            | Statement::WhileLoop { label, block, .. }
+            | Statement::Loop { label, block } => {
+                loops.push(label.as_deref());
+                collect_jumps(block, loops, 0, jumps);
+                loops.pop();
+            }
+            Statement::Local { variable, .. } => {
+                if let Expression::Init { right, .. } = &**variable {
+                    collect_expression_jumps(right, loops, switch_depth, jumps);
+                }
+            }
+            Statement::Expression { expression }
+            | Statement::ImplicitReturn { expression }
+            | Statement::Return {
+                value: Some(expression),
+            } => collect_expression_jumps(expression, loops, switch_depth, jumps),
+            Statement::Return { value: None } => {}
+        }
+    }
+}
+
+/// Collect the jumps in the statements of a block, branch or `switch` expression
+fn collect_expression_jumps<'a>(
+    expression: &'a Expression,
+    loops: &mut Vec<Option<&'a str>>,
+    switch_depth: usize,
+    jumps: &mut Vec<Jump>,
+) {
+    match expression {
+        Expression::Block { block, .. } => collect_jumps(block, loops, switch_depth, jumps),
+        Expression::Assigment { right, .. } => {
+            collect_expression_jumps(right, loops, switch_depth, jumps)
+        }
+        Expression::Branch {
+            then_block,
+            else_block,
+            ..
+        } => {
+            collect_jumps(then_block, loops, switch_depth, jumps);
+            if let Some(else_block) = else_block {
+                collect_jumps(else_block, loops, switch_depth, jumps);
+            }
+        }
+        Expression::Switch { cases, default, .. } => {
+            for (_, block) in cases {
+                collect_jumps(block, loops, switch_depth + 1, jumps);
+            }
+            collect_jumps(default, loops, switch_depth + 1, jumps);
+        }
+        _ => {}
+    }
+}
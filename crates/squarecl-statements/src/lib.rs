@@ -5,6 +5,8 @@ pub mod codegen;
 const ALPHA: u32 = 10;
 
 #[square]
+// Kernels only run through their expansion, so the Rust body is never executed
+#[allow(unused_variables, unused_assignments)]
 pub fn test_kernel(a: u32, b: u32) {
     let mut d: u32 = 0;
     let a = a * b;
@@ -0,0 +1,45 @@
+use squarecl_macros::square;
+use squarecl_statements::codegen::WgpuKernel;
+
+#[square]
+pub fn labelled(n: u32) {
+    let mut x = 0;
+    'outer: for i in 0..n {
+        loop {
+            if x > i {
+                continue 'outer;
+            }
+            match x {
+                4 => break 'outer,
+                5 => continue,
+                _ => {}
+            }
+            x += 1;
+        }
+    }
+}
+
+/// Only the jumps are compared, since the rest of the output depends on the global local counter
+#[test]
+fn labelled_jumps() {
+    let shader = WgpuKernel(labelled::expand()).to_string();
+    let jumps = shader
+        .lines()
+        .filter(|line| line.contains("break") || line.contains("continue"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        jumps,
+        [
+            "var _break_0 = false;",
+            "var _continue_0 = false;",
+            "_continue_0 = true;",
+            "break;",
+            "_break_0 = true;",
+            "break;",
+            "continue;",
+            "if _break_0 { break; }",
+            "if _continue_0 { continue; }",
+            "if _break_0 { break; }",
+        ]
+    );
+}
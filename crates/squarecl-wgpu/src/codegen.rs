@@ -1,39 +1,261 @@
-use std::fmt::{Display, Error, Formatter};
+use std::{
+    cell::Cell,
+    fmt::{Display, Error, Formatter},
+};
 
 use derive_more::derive::Deref;
-use squarecl_core::ir::{Expression, IRType, Operator, Statement};
+use squarecl_core::ir::{Expression, Function, IRType, Intrinsic, Operator, Statement, StructType};
+
+pub struct WgpuKernel(pub Function);
+
+#[derive(Deref)]
+struct WgpuStatement<'a>(#[deref] &'a Statement, JumpScope<'a>);
 
-pub struct WgpuKernel(pub Vec<Statement>);
 #[derive(Deref)]
-struct WgpuStatement<'a>(&'a Statement);
+struct WgpuBlock<'a>(#[deref] &'a [Statement], JumpScope<'a>);
 
 #[derive(Deref)]
-struct WgpuExpression<'a>(&'a Box<Expression>);
+struct WgpuFunction<'a>(&'a Function);
 
 #[derive(Deref)]
-struct WgpuOperator<'a>(&'a Operator);
+struct WgpuExpression<'a>(&'a Expression);
+
+/// Operators are printed with the type of their (left) operand
+#[derive(Deref)]
+struct WgpuOperator<'a>(#[deref] &'a Operator, IRType);
 
 struct WgpuType(IRType);
 
-fn e(expr: &Box<Expression>) -> WgpuExpression<'_> {
+fn e(expr: &Expression) -> WgpuExpression<'_> {
     WgpuExpression(expr)
 }
 
-fn o(expr: &Operator) -> WgpuOperator<'_> {
-    WgpuOperator(expr)
+/// Operands are parenthesized when nested, since the tree doesn't keep the source's parentheses
+/// and WGSL requires them for some operator combinations.
+fn p(expr: &Expression) -> String {
+    match expr {
+        Expression::Binary { .. } => format!("({})", e(expr)),
+        _ => e(expr).to_string(),
+    }
+}
+
+/// WGSL only accepts `u32` shift amounts
+fn right_operand(operator: &Operator, right: &Expression) -> String {
+    match operator {
+        Operator::Shl | Operator::Shr if !matches!(right.ir_type(), IRType::UInt(32)) => {
+            format!("u32({})", e(right))
+        }
+        _ => p(right),
+    }
+}
+
+fn write_intrinsic(
+    f: &mut Formatter<'_>,
+    intrinsic: Intrinsic,
+    args: &[Expression],
+) -> std::fmt::Result {
+    let name = match intrinsic {
+        Intrinsic::Sqrt => "sqrt",
+        Intrinsic::Exp => "exp",
+        Intrinsic::Ln => "log",
+        Intrinsic::Log2 => "log2",
+        Intrinsic::Sin => "sin",
+        Intrinsic::Cos => "cos",
+        Intrinsic::Tanh => "tanh",
+        Intrinsic::Powf => "pow",
+        Intrinsic::Abs => "abs",
+        Intrinsic::Floor => "floor",
+        Intrinsic::Ceil => "ceil",
+        Intrinsic::Min => "min",
+        Intrinsic::Max => "max",
+        Intrinsic::Clamp => "clamp",
+        Intrinsic::MulAdd => "fma",
+        // WGSL `round` rounds half-way cases to even, so Rust's rounding away from zero is
+        // rebuilt from `trunc`
+        Intrinsic::Round => {
+            let x = e(&args[0]);
+            return write!(
+                f,
+                "(trunc({x}) + select(0.0, sign({x}), abs({x} - trunc({x})) >= 0.5))"
+            );
+        }
+        Intrinsic::Recip => return write!(f, "(1.0 / {})", p(&args[0])),
+        // WGSL's `pow` is only defined for positive floats, so integer exponents use a helper
+        Intrinsic::Pow | Intrinsic::Powi => {
+            let helper = Helper::Pow(args[0].ir_type());
+            return write!(f, "{helper}({}, {})", e(&args[0]), e(&args[1]));
+        }
+    };
+    let args = args
+        .iter()
+        .map(|arg| e(arg).to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    write!(f, "{name}({args})")
+}
+
+fn o(expr: &Operator, ty: IRType) -> WgpuOperator<'_> {
+    WgpuOperator(expr, ty)
 }
 
 impl Display for WgpuKernel {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "fn main(a: u32, b: u32) {{")?;
-        for statement in &self.0 {
-            let statement = WgpuStatement(statement);
-            write!(f, "{statement}")?;
+        let mut functions = Vec::new();
+        collect_functions(&self.0.body, &mut functions);
+        let mut functions = functions.into_iter().cloned().collect::<Vec<_>>();
+        let mut kernel = self.0.clone();
+        let mut counter = 0;
+        for function in functions.iter_mut().chain([&mut kernel]) {
+            rename_shared_memory(function, &mut counter);
         }
+        if kernel.bounds_checks {
+            let mut lengths = kernel
+                .bindings
+                .iter()
+                .map(|binding| (binding.name.clone(), Length::Runtime))
+                .collect::<Vec<_>>();
+            for function in functions.iter().chain([&kernel]) {
+                let mut shared_memory = Vec::new();
+                collect_shared_memory(&function.body, &mut shared_memory);
+                lengths.extend(
+                    shared_memory
+                        .into_iter()
+                        .map(|(name, _, size)| (name.to_string(), Length::Fixed(size))),
+                );
+            }
+            for function in functions.iter_mut().chain([&mut kernel]) {
+                check_bounds(&mut function.body, &lengths);
+            }
+        }
+
+        let mut structs = Vec::new();
+        for function in functions.iter().chain([&kernel]) {
+            collect_structs(function, &mut structs);
+        }
+        for ty in structs {
+            writeln!(f, "struct {} {{", ty.name)?;
+            for (name, ty) in ty.fields.iter() {
+                writeln!(f, "{}{name}: {},", MemberLayout(*ty), WgpuType(*ty))?;
+            }
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+        // Bools can't be stored in uniforms, so parameters containing them are bound as a copy
+        // with `u32`s instead
+        let mut host_structs = Vec::new();
+        for (_, ty) in kernel.params.iter() {
+            collect_host_structs(*ty, &mut host_structs);
+        }
+        for ty in host_structs {
+            writeln!(f, "struct {} {{", HostType(IRType::Struct(ty)))?;
+            for (name, ty) in ty.fields.iter() {
+                writeln!(f, "{}{name}: {},", MemberLayout(*ty), HostType(*ty))?;
+            }
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+
+        for (index, binding) in kernel.bindings.iter().enumerate() {
+            let access = if binding.mutable {
+                "read_write"
+            } else {
+                "read"
+            };
+            let ty = WgpuType(binding.ty);
+            writeln!(
+                f,
+                "@group(0) @binding({index}) var<storage, {access}> {}: array<{ty}>;",
+                binding.name
+            )?;
+        }
+        // Scalar parameters are bound as uniforms after the storage buffers, since entry points
+        // can only take builtins
+        for (index, (name, ty)) in kernel.params.iter().enumerate() {
+            let index = kernel.bindings.len() + index;
+            let ty = HostType(*ty);
+            writeln!(f, "@group(0) @binding({index}) var<uniform> {name}: {ty};")?;
+        }
+        if !kernel.bindings.is_empty() || !kernel.params.is_empty() {
+            writeln!(f)?;
+        }
+
+        // Shared memory can only be declared at module scope
+        let mut shared_memory = Vec::new();
+        for function in functions.iter().chain([&kernel]) {
+            collect_shared_memory(&function.body, &mut shared_memory);
+        }
+        for (name, ty, size) in shared_memory.iter() {
+            let ty = WgpuType(*ty);
+            writeln!(f, "var<workgroup> {name}: array<{ty}, {size}>;")?;
+        }
+        if !shared_memory.is_empty() {
+            writeln!(f)?;
+        }
+
+        let mut helpers = Vec::new();
+        for function in functions.iter().chain([&kernel]) {
+            collect_helpers(&function.body, &mut helpers);
+        }
+        for helper in helpers {
+            writeln!(f, "{}", HelperFunction(helper))?;
+        }
+
+        for function in functions.iter() {
+            writeln!(f, "{}", WgpuFunction(function))?;
+        }
+        // Only functions without a return value can be entry points, others are just helpers
+        if !matches!(kernel.return_type, IRType::Unit) {
+            return writeln!(f, "{}", WgpuFunction(&kernel));
+        }
+        let [x, y, z] = kernel.workgroup_size;
+        writeln!(f, "@compute @workgroup_size({x}, {y}, {z})")?;
+        writeln!(f, "fn {}() {{", kernel.entry_name())?;
+        // The converted value shadows the uniform, the initializer still refers to the uniform
+        for (name, ty) in kernel.params.iter() {
+            if needs_host_type(*ty) {
+                let value = from_host(name.clone(), *ty);
+                writeln!(f, "let {name}: {} = {value};", WgpuType(*ty))?;
+            }
+        }
+        let temporaries = Cell::new(0);
+        write!(
+            f,
+            "{}",
+            WgpuBlock(&kernel.body, JumpScope::new(&temporaries))
+        )?;
         writeln!(f, "}}")
     }
 }
 
+impl<'a> Display for WgpuFunction<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {}", WgpuType(*ty)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let returns = match self.return_type {
+            IRType::Unit => "".to_string(),
+            ty => format!(" -> {}", WgpuType(ty)),
+        };
+        writeln!(f, "fn {}({params}){returns} {{", self.name)?;
+        let temporaries = Cell::new(0);
+        write!(f, "{}", WgpuBlock(&self.body, JumpScope::new(&temporaries)))?;
+        writeln!(f, "}}")
+    }
+}
+
+impl<'a> Display for WgpuBlock<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for statement in self.0 {
+            let statement = WgpuStatement(statement, self.1);
+            write!(f, "{statement}")?;
+        }
+        Ok(())
+    }
+}
+
 impl<'a> Display for WgpuStatement<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match &self.0 {
@@ -44,7 +266,21 @@ impl<'a> Display for WgpuStatement<'a> {
             } => {
                 let variable = e(variable);
                 let keyword = if *mutable { "var" } else { "let" };
-                match &**variable.0 {
+                match variable.0 {
+                    // Declared at module scope by the kernel
+                    Expression::Init { right, .. }
+                        if matches!(**right, Expression::SharedMemory { .. }) =>
+                    {
+                        Ok(())
+                    }
+                    // WGSL has no block expressions, so the variable is assigned at the end of the
+                    // block instead
+                    Expression::Init { left, right, .. } if contains_block(right) => {
+                        let ty = WgpuType(ty.unwrap_or(right.ir_type()));
+                        let left = e(left);
+                        writeln!(f, "var {left}: {ty};")?;
+                        write_value(f, right, &format!("{left} = "), self.1)
+                    }
                     Expression::Init { left, right, .. } => {
                         let ty = ty
                             .map(WgpuType)
@@ -54,44 +290,1343 @@ impl<'a> Display for WgpuStatement<'a> {
                         let right = e(right);
                         writeln!(f, "{keyword} {left}{ty} = {right};")
                     }
+                    // `let` requires an initializer, so declarations assigned later are `var`
                     _ => {
                         // Prefer explicit type
                         let ty = WgpuType(ty.unwrap_or(variable.ir_type()));
-                        writeln!(f, "{keyword} {variable}: {ty};") // TODO: Type
+                        writeln!(f, "var {variable}: {ty};")
                     }
                 }
             }
+            Statement::Expression { expression } => match &**expression {
+                Expression::Branch {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    let mut hoisted = Vec::new();
+                    let condition = hoist_blocks(condition, &mut hoisted, self.1);
+                    write_hoisted(f, &hoisted, self.1, |f| {
+                        write_branch(f, &condition, then_block, else_block.as_deref(), self.1)?;
+                        writeln!(f)
+                    })
+                }
+                Expression::Switch {
+                    value,
+                    cases,
+                    default,
+                } => {
+                    let mut hoisted = Vec::new();
+                    let value = hoist_blocks(value, &mut hoisted, self.1);
+                    write_hoisted(f, &hoisted, self.1, |f| {
+                        write_switch(f, &value, cases, default, self.1)
+                    })
+                }
+                Expression::Block { .. } => write_value(f, expression, "_ = ", self.1),
+                Expression::Assigment { left, right, .. }
+                    if matches!(**right, Expression::Block { .. }) =>
+                {
+                    write_value(f, right, &format!("{} = ", e(left)), self.1)
+                }
+                expression => write_value(f, expression, "", self.1),
+            },
+            Statement::ImplicitReturn { expression } => {
+                write_value(f, expression, "return ", self.1)
+            }
+            Statement::Return { value: Some(value) } => write_value(f, value, "return ", self.1),
+            Statement::Return { value: None } => writeln!(f, "return;"),
+            Statement::ForLoop {
+                label,
+                variable,
+                start,
+                end,
+                step,
+                inclusive,
+                block,
+            } => {
+                // The bounds are bound before the loop so they're only evaluated once, even if the
+                // body changes what they read. The loop runs on a hidden counter that's copied into
+                // the loop variable, so the body can't change the number of iterations either.
+                let mut hoisted = Vec::new();
+                let start = hoist_blocks(start, &mut hoisted, self.1);
+                let end = hoist_blocks(end, &mut hoisted, self.1);
+                let step = step
+                    .as_ref()
+                    .map(|step| hoist_blocks(step, &mut hoisted, self.1));
+
+                let ty = variable.ir_type();
+                let counter = Expression::Variable {
+                    name: self.1.unique("i"),
+                    ty,
+                };
+                let mut body = vec![Statement::Local {
+                    variable: Box::new(Expression::Init {
+                        left: variable.clone(),
+                        right: Box::new(counter.clone()),
+                        ty,
+                    }),
+                    mutable: true,
+                    ty: Some(ty),
+                }];
+                body.extend(block.iter().cloned());
+
+                let counter = e(&counter);
+                let ty = WgpuType(ty);
+                let start = e(&start);
+                let comparison = if *inclusive { "<=" } else { "<" };
+                write_hoisted(f, &hoisted, self.1, |f| {
+                    let end = bind_bound(f, &end, "end", self.1)?;
+                    let increment = match &step {
+                        Some(step) => {
+                            format!("{counter} += {}", bind_bound(f, step, "step", self.1)?)
+                        }
+                        None => format!("{counter}++"),
+                    };
+                    let header = format!(
+                        "for (var {counter}: {ty} = {start}; {counter} {comparison} {end}; {increment}) {{"
+                    );
+                    write_loop(f, label.as_deref(), &header, None, &body, self.1)
+                })
+            }
+            Statement::WhileLoop {
+                label,
+                condition,
+                block,
+            } => {
+                let label = label.as_deref();
+                write_loop(f, label, "loop {", Some(condition), block, self.1)
+            }
+            Statement::Loop { label, block } => {
+                write_loop(f, label.as_deref(), "loop {", None, block, self.1)
+            }
+            Statement::Break { label } => {
+                let jump = self.1.jump(JumpKind::Break, label.as_deref());
+                self.1.write_jump(f, jump)
+            }
+            Statement::Continue { label } => {
+                let jump = self.1.jump(JumpKind::Continue, label.as_deref());
+                self.1.write_jump(f, jump)
+            }
+        }
+    }
+}
+
+/// Write a block in its own scope. The value of the block, if any, is written as a statement
+/// prefixed by `assign`.
+fn write_block(
+    f: &mut Formatter<'_>,
+    block: &[Statement],
+    assign: &str,
+    scope: JumpScope,
+) -> Result<(), Error> {
+    writeln!(f, "{{")?;
+    match block.split_last() {
+        Some((Statement::ImplicitReturn { expression }, statements)) => {
+            write!(f, "{}", WgpuBlock(statements, scope))?;
+            write_value(f, expression, assign, scope)?;
+        }
+        _ => write!(f, "{}", WgpuBlock(block, scope))?,
+    }
+    writeln!(f, "}}")
+}
+
+fn write_value(
+    f: &mut Formatter<'_>,
+    value: &Expression,
+    assign: &str,
+    scope: JumpScope,
+) -> Result<(), Error> {
+    match value {
+        Expression::Block { block, .. } => write_block(f, block, assign, scope),
+        value => {
+            let mut hoisted = Vec::new();
+            let value = hoist_blocks(value, &mut hoisted, scope);
+            write_hoisted(f, &hoisted, scope, |f| {
+                writeln!(f, "{assign}{};", e(&value))
+            })
+        }
+    }
+}
+
+/// Blocks nested in other expressions, like inlined calls, are hoisted into variables that are
+/// assigned before the expression is evaluated. Returns the expression with the blocks replaced.
+fn hoist_blocks(
+    expression: &Expression,
+    hoisted: &mut Vec<(String, Expression)>,
+    scope: JumpScope,
+) -> Expression {
+    let mut hoist = |expression: &Expression| Box::new(hoist_blocks(expression, hoisted, scope));
+    match expression {
+        Expression::Block { ty, .. } => {
+            let name = scope.temporary();
+            hoisted.push((name.clone(), expression.clone()));
+            Expression::Variable { name, ty: *ty }
+        }
+        // The right operand of a logical operator is only evaluated depending on the left one, so
+        // its blocks are hoisted into a branch that computes the whole operation
+        Expression::Binary {
+            left,
+            operator: operator @ (Operator::And | Operator::Or),
+            right,
+            ty,
+        } if contains_block(right) => {
+            let left = hoist(left);
+            let name = scope.temporary();
+            let variable = Expression::Variable {
+                name: name.clone(),
+                ty: *ty,
+            };
+            let assign = |value: Expression| Statement::Expression {
+                expression: Box::new(Expression::Assigment {
+                    left: Box::new(variable.clone()),
+                    right: Box::new(value),
+                    ty: *ty,
+                }),
+            };
+            // `left && right` is `false` if `left` is, `left || right` is `true` if `left` is
+            let (condition, short_circuit) = match operator {
+                Operator::And => (left, "false"),
+                _ => (
+                    Box::new(Expression::Unary {
+                        input: left,
+                        operator: Operator::Not,
+                        ty: *ty,
+                    }),
+                    "true",
+                ),
+            };
+            let block = vec![
+                assign(Expression::Literal {
+                    value: short_circuit.to_string(),
+                    ty: *ty,
+                }),
+                Statement::Expression {
+                    expression: Box::new(Expression::Branch {
+                        condition,
+                        then_block: vec![assign(*right.clone())],
+                        else_block: None,
+                    }),
+                },
+            ];
+            hoisted.push((name, Expression::Block { block, ty: *ty }));
+            variable
+        }
+        Expression::Binary {
+            left,
+            operator,
+            right,
+            ty,
+        } => Expression::Binary {
+            left: hoist(left),
+            operator: *operator,
+            right: hoist(right),
+            ty: *ty,
+        },
+        Expression::Unary {
+            input,
+            operator,
+            ty,
+        } => Expression::Unary {
+            input: hoist(input),
+            operator: *operator,
+            ty: *ty,
+        },
+        Expression::Assigment { left, right, ty } => Expression::Assigment {
+            left: hoist(left),
+            right: hoist(right),
+            ty: *ty,
+        },
+        Expression::CompoundAssignment {
+            left,
+            operator,
+            right,
+            ty,
+        } => Expression::CompoundAssignment {
+            left: hoist(left),
+            operator: *operator,
+            right: hoist(right),
+            ty: *ty,
+        },
+        Expression::Cast { input, from, to } => Expression::Cast {
+            input: hoist(input),
+            from: *from,
+            to: *to,
+        },
+        Expression::Intrinsic {
+            intrinsic,
+            args,
+            ty,
+        } => Expression::Intrinsic {
+            intrinsic: *intrinsic,
+            args: args.iter().map(|arg| *hoist(arg)).collect(),
+            ty: *ty,
+        },
+        Expression::Index { array, index, ty } => Expression::Index {
+            array: hoist(array),
+            index: hoist(index),
+            ty: *ty,
+        },
+        Expression::Tuple { elements, ty } => Expression::Tuple {
+            elements: elements.iter().map(|element| *hoist(element)).collect(),
+            ty: *ty,
+        },
+        Expression::Field { base, name, ty } => Expression::Field {
+            base: hoist(base),
+            name: name.clone(),
+            ty: *ty,
+        },
+        Expression::Call { function, args, ty } => Expression::Call {
+            function: function.clone(),
+            args: args.iter().map(|arg| *hoist(arg)).collect(),
+            ty: *ty,
+        },
+        Expression::Variable { .. }
+        | Expression::Literal { .. }
+        | Expression::SharedMemory { .. }
+        | Expression::Init { .. }
+        | Expression::Branch { .. }
+        | Expression::Switch { .. } => expression.clone(),
+    }
+}
+
+/// Bind a loop bound to a `let` so it's only evaluated once. Literals are used directly.
+fn bind_bound(
+    f: &mut Formatter<'_>,
+    bound: &Expression,
+    name: &str,
+    scope: JumpScope,
+) -> Result<String, Error> {
+    if let Expression::Literal { .. } = bound {
+        return Ok(e(bound).to_string());
+    }
+    let name = scope.unique(name);
+    writeln!(
+        f,
+        "let {name}: {} = {};",
+        WgpuType(bound.ir_type()),
+        e(bound)
+    )?;
+    Ok(name)
+}
+
+/// Write the hoisted blocks followed by the statement using them, in their own scope
+fn write_hoisted(
+    f: &mut Formatter<'_>,
+    hoisted: &[(String, Expression)],
+    scope: JumpScope,
+    write: impl FnOnce(&mut Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    if hoisted.is_empty() {
+        return write(f);
+    }
+    writeln!(f, "{{")?;
+    for (name, block) in hoisted {
+        writeln!(f, "var {name}: {};", WgpuType(block.ir_type()))?;
+        write_value(f, block, &format!("{name} = "), scope)?;
+    }
+    write(f)?;
+    writeln!(f, "}}")
+}
+
+fn contains_block(expression: &Expression) -> bool {
+    let mut found = false;
+    visit_expression(expression, &mut |expression| {
+        found |= matches!(expression, Expression::Block { .. });
+    });
+    found
+}
+
+/// Visit every expression in a block, including sub-expressions and nested blocks. Called
+/// functions aren't entered.
+fn visit_block<'a>(block: &'a [Statement], visit: &mut dyn FnMut(&'a Expression)) {
+    for statement in block {
+        match statement {
+            Statement::Local { variable, .. } => visit_expression(variable, visit),
+            Statement::Expression { expression }
+            | Statement::ImplicitReturn { expression }
+            | Statement::Return {
+                value: Some(expression),
+            } => visit_expression(expression, visit),
+            Statement::ForLoop {
+                start,
+                end,
+                step,
+                block,
+                ..
+            } => {
+                visit_expression(start, visit);
+                visit_expression(end, visit);
+                if let Some(step) = step {
+                    visit_expression(step, visit);
+                }
+                visit_block(block, visit);
+            }
+            Statement::WhileLoop {
+                condition, block, ..
+            } => {
+                visit_expression(condition, visit);
+                visit_block(block, visit);
+            }
+            Statement::Loop { block, .. } => visit_block(block, visit),
+            Statement::Return { value: None }
+            | Statement::Break { .. }
+            | Statement::Continue { .. } => {}
+        }
+    }
+}
+
+fn visit_expression<'a>(expression: &'a Expression, visit: &mut dyn FnMut(&'a Expression)) {
+    visit(expression);
+    match expression {
+        Expression::Index {
+            array: left,
+            index: right,
+            ..
+        }
+        | Expression::Binary { left, right, .. }
+        | Expression::Assigment { left, right, .. }
+        | Expression::CompoundAssignment { left, right, .. }
+        | Expression::Init { left, right, .. } => {
+            visit_expression(left, visit);
+            visit_expression(right, visit);
+        }
+        Expression::Unary { input, .. }
+        | Expression::Cast { input, .. }
+        | Expression::Field { base: input, .. } => visit_expression(input, visit),
+        Expression::Intrinsic { args, .. }
+        | Expression::Call { args, .. }
+        | Expression::Tuple { elements: args, .. } => {
+            for arg in args {
+                visit_expression(arg, visit);
+            }
+        }
+        Expression::Block { block, .. } => visit_block(block, visit),
+        Expression::Branch {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            visit_expression(condition, visit);
+            visit_block(then_block, visit);
+            if let Some(else_block) = else_block {
+                visit_block(else_block, visit);
+            }
+        }
+        Expression::Switch {
+            value,
+            cases,
+            default,
+        } => {
+            visit_expression(value, visit);
+            for (_, block) in cases {
+                visit_block(block, visit);
+            }
+            visit_block(default, visit);
+        }
+        Expression::Variable { .. }
+        | Expression::Literal { .. }
+        | Expression::SharedMemory { .. } => {}
+    }
+}
+
+/// Collect all functions called from a block, including indirect calls. Each function is only
+/// collected once.
+fn collect_functions<'a>(block: &'a [Statement], functions: &mut Vec<&'a Function>) {
+    let mut called = Vec::new();
+    visit_block(block, &mut |expression| {
+        if let Expression::Call { function, .. } = expression {
+            called.push(&**function);
+        }
+    });
+    for function in called {
+        if functions.iter().all(|other| other.name != function.name) {
+            functions.push(function);
+            collect_functions(&function.body, functions);
+        }
+    }
+}
+
+/// Collect the struct types used by a function. Structs are collected after the structs used by
+/// their fields, since WGSL doesn't allow forward references in struct members.
+fn collect_structs(function: &Function, structs: &mut Vec<&'static StructType>) {
+    fn push(ty: IRType, structs: &mut Vec<&'static StructType>) {
+        if let IRType::Struct(ty) = ty {
+            if structs.iter().all(|other| other.name != ty.name) {
+                for (_, field) in ty.fields.iter() {
+                    push(*field, structs);
+                }
+                structs.push(ty);
+            }
+        }
+    }
+
+    for (_, ty) in function.params.iter() {
+        push(*ty, structs);
+    }
+    for binding in function.bindings.iter() {
+        push(binding.ty, structs);
+    }
+    push(function.return_type, structs);
+    visit_block(&function.body, &mut |expression| {
+        push(expression.ir_type(), structs)
+    });
+}
+
+/// Collect the shared memory allocated in a block as `(name, element type, size)`. Names are made
+/// unique by `rename_shared_memory` first.
+fn collect_shared_memory<'a>(block: &'a [Statement], shared: &mut Vec<(&'a str, IRType, u32)>) {
+    visit_block(block, &mut |expression| {
+        if let Expression::Init { left, right, .. } = expression {
+            if let (Expression::Variable { name, .. }, Expression::SharedMemory { size, ty }) =
+                (&**left, &**right)
+            {
+                shared.push((name, *ty, *size));
+            }
+        }
+    });
+}
+
+/// Give each shared memory allocation of a function a unique name at module scope, like
+/// `reduce_tile_0`, and rename its uses. `counter` is shared by all functions of the kernel, so
+/// allocations with the same name in different functions or scopes don't collide.
+fn rename_shared_memory(function: &mut Function, counter: &mut usize) {
+    let mut renames = Vec::new();
+    let name = function.name.clone();
+    rename_block(&mut function.body, &name, &mut renames, counter);
+}
+
+/// Rename the uses of shared memory in a block. `renames` maps the names in scope to their new
+/// name, innermost last, so locals shadowing an allocation keep their own name.
+fn rename_block(
+    block: &mut [Statement],
+    function: &str,
+    renames: &mut Vec<(String, String)>,
+    counter: &mut usize,
+) {
+    let outer = renames.len();
+    for statement in block {
+        match statement {
+            Statement::Local { variable, .. } => match &mut **variable {
+                Expression::Init { left, right, .. } => {
+                    // The initializer can still see a shadowed variable
+                    rename_expression(right, function, renames, counter);
+                    if let Expression::Variable { name, .. } = &mut **left {
+                        let renamed = match **right {
+                            Expression::SharedMemory { .. } => {
+                                *counter += 1;
+                                format!("{function}_{name}_{}", *counter - 1)
+                            }
+                            _ => name.clone(),
+                        };
+                        renames.push((name.clone(), renamed.clone()));
+                        *name = renamed;
+                    }
+                }
+                Expression::Variable { name, .. } => renames.push((name.clone(), name.clone())),
+                variable => rename_expression(variable, function, renames, counter),
+            },
+            Statement::Expression { expression }
+            | Statement::ImplicitReturn { expression }
+            | Statement::Return {
+                value: Some(expression),
+            } => rename_expression(expression, function, renames, counter),
+            Statement::ForLoop {
+                variable,
+                start,
+                end,
+                step,
+                block,
+                ..
+            } => {
+                rename_expression(start, function, renames, counter);
+                rename_expression(end, function, renames, counter);
+                if let Some(step) = step {
+                    rename_expression(step, function, renames, counter);
+                }
+                if let Expression::Variable { name, .. } = &**variable {
+                    renames.push((name.clone(), name.clone()));
+                }
+                rename_block(block, function, renames, counter);
+                renames.pop();
+            }
+            Statement::WhileLoop {
+                condition, block, ..
+            } => {
+                rename_expression(condition, function, renames, counter);
+                rename_block(block, function, renames, counter);
+            }
+            Statement::Loop { block, .. } => rename_block(block, function, renames, counter),
+            Statement::Return { value: None }
+            | Statement::Break { .. }
+            | Statement::Continue { .. } => {}
+        }
+    }
+    renames.truncate(outer);
+}
+
+fn rename_expression(
+    expression: &mut Expression,
+    function: &str,
+    renames: &mut Vec<(String, String)>,
+    counter: &mut usize,
+) {
+    match expression {
+        Expression::Variable { name, .. } => {
+            if let Some((_, renamed)) = renames.iter().rev().find(|(old, _)| old == name) {
+                *name = renamed.clone();
+            }
+        }
+        Expression::Index {
+            array: left,
+            index: right,
+            ..
+        }
+        | Expression::Binary { left, right, .. }
+        | Expression::Assigment { left, right, .. }
+        | Expression::CompoundAssignment { left, right, .. }
+        | Expression::Init { left, right, .. } => {
+            rename_expression(left, function, renames, counter);
+            rename_expression(right, function, renames, counter);
+        }
+        Expression::Unary { input, .. }
+        | Expression::Cast { input, .. }
+        | Expression::Field { base: input, .. } => {
+            rename_expression(input, function, renames, counter)
+        }
+        Expression::Intrinsic { args, .. }
+        | Expression::Call { args, .. }
+        | Expression::Tuple { elements: args, .. } => {
+            for arg in args {
+                rename_expression(arg, function, renames, counter);
+            }
+        }
+        Expression::Block { block, .. } => rename_block(block, function, renames, counter),
+        Expression::Branch {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            rename_expression(condition, function, renames, counter);
+            rename_block(then_block, function, renames, counter);
+            if let Some(else_block) = else_block {
+                rename_block(else_block, function, renames, counter);
+            }
+        }
+        Expression::Switch {
+            value,
+            cases,
+            default,
+        } => {
+            rename_expression(value, function, renames, counter);
+            for (_, block) in cases {
+                rename_block(block, function, renames, counter);
+            }
+            rename_block(default, function, renames, counter);
+        }
+        Expression::Literal { .. } | Expression::SharedMemory { .. } => {}
+    }
+}
+
+/// Length of an array whose accesses are checked
+#[derive(Clone, Copy)]
+enum Length {
+    /// Storage buffer, sized when it's bound
+    Runtime,
+    /// Shared memory
+    Fixed(u32),
+}
+
+/// Guard the array accesses of a block. Reads are clamped to the last element and writes are
+/// skipped when out of bounds. Constant indices into shared memory are checked up front.
+fn check_bounds(block: &mut [Statement], lengths: &[(String, Length)]) {
+    for statement in block {
+        match statement {
+            Statement::Local { variable, .. } => clamp_reads(variable, lengths),
             Statement::Expression { expression } => {
-                let expression = e(expression);
-                writeln!(f, "{expression};")
+                clamp_reads(expression, lengths);
+                let guard = match &**expression {
+                    Expression::Assigment { left, .. }
+                    | Expression::CompoundAssignment { left, .. } => guard(left, lengths),
+                    _ => None,
+                };
+                if let Some(condition) = guard {
+                    let write = Statement::Expression {
+                        expression: expression.clone(),
+                    };
+                    **expression = Expression::Branch {
+                        condition: Box::new(condition),
+                        then_block: vec![write],
+                        else_block: None,
+                    };
+                }
             }
-            Statement::ImplicitReturn { expression } => {
-                let expression = e(expression);
-                writeln!(f, "return {expression};")
+            Statement::ImplicitReturn { expression }
+            | Statement::Return {
+                value: Some(expression),
+            } => clamp_reads(expression, lengths),
+            Statement::ForLoop {
+                start,
+                end,
+                step,
+                block,
+                ..
+            } => {
+                clamp_reads(start, lengths);
+                clamp_reads(end, lengths);
+                if let Some(step) = step {
+                    clamp_reads(step, lengths);
+                }
+                check_bounds(block, lengths);
+            }
+            Statement::WhileLoop {
+                condition, block, ..
+            } => {
+                clamp_reads(condition, lengths);
+                check_bounds(block, lengths);
+            }
+            Statement::Loop { block, .. } => check_bounds(block, lengths),
+            Statement::Return { value: None }
+            | Statement::Break { .. }
+            | Statement::Continue { .. } => {}
+        }
+    }
+}
+
+/// Name and length of an indexed array, unless the index is known to be in bounds
+fn checked_array<'a>(
+    array: &'a Expression,
+    index: &Expression,
+    lengths: &[(String, Length)],
+) -> Option<(&'a str, Length)> {
+    let Expression::Variable { name, .. } = array else {
+        return None;
+    };
+    let (_, length) = lengths.iter().find(|(array, _)| array == name)?;
+    match (length, constant_index(index)) {
+        (Length::Fixed(size), Some(index)) if index < *size => None,
+        _ => Some((name, *length)),
+    }
+}
+
+fn constant_index(index: &Expression) -> Option<u32> {
+    match index {
+        Expression::Literal { value, .. } => value.parse().ok(),
+        _ => None,
+    }
+}
+
+fn length_expression(array: &str, length: Length) -> Expression {
+    match length {
+        // Emitted as is
+        Length::Runtime => Expression::Variable {
+            name: format!("arrayLength(&{array})"),
+            ty: IRType::UInt(32),
+        },
+        Length::Fixed(size) => u32_literal(size),
+    }
+}
+
+fn last_index(array: &str, length: Length) -> Expression {
+    match length {
+        Length::Runtime => Expression::Binary {
+            left: Box::new(length_expression(array, length)),
+            operator: Operator::Sub,
+            right: Box::new(u32_literal(1)),
+            ty: IRType::UInt(32),
+        },
+        Length::Fixed(size) => u32_literal(size.saturating_sub(1)),
+    }
+}
+
+fn u32_literal(value: u32) -> Expression {
+    Expression::Literal {
+        value: value.to_string(),
+        ty: IRType::UInt(32),
+    }
+}
+
+/// Indices are compared as `u32`, so negative indices are out of bounds
+fn as_u32(index: Expression) -> Expression {
+    if let Some(index) = constant_index(&index) {
+        return u32_literal(index);
+    }
+    match index.ir_type() {
+        IRType::UInt(32) => index,
+        from => Expression::Cast {
+            input: Box::new(index),
+            from,
+            to: IRType::UInt(32),
+        },
+    }
+}
+
+/// Condition under which the target of an assignment is in bounds
+fn guard(target: &Expression, lengths: &[(String, Length)]) -> Option<Expression> {
+    match target {
+        Expression::Index { array, index, .. } => {
+            let (array, length) = checked_array(array, index, lengths)?;
+            Some(Expression::Binary {
+                left: Box::new(as_u32(*index.clone())),
+                operator: Operator::Lt,
+                right: Box::new(length_expression(array, length)),
+                ty: IRType::Bool,
+            })
+        }
+        Expression::Field { base, .. } => guard(base, lengths),
+        _ => None,
+    }
+}
+
+fn clamp_reads(expression: &mut Expression, lengths: &[(String, Length)]) {
+    match expression {
+        // Assignment targets are guarded by the statement, only the indices are reads
+        Expression::Assigment { left, right, .. }
+        | Expression::CompoundAssignment { left, right, .. } => {
+            clamp_target(left, lengths);
+            clamp_reads(right, lengths);
+        }
+        Expression::Index { array, index, .. } => {
+            clamp_reads(index, lengths);
+            if let Some((array, length)) = checked_array(array, index, lengths) {
+                let last = last_index(array, length);
+                let input = std::mem::replace(&mut **index, last.clone());
+                **index = Expression::Intrinsic {
+                    intrinsic: Intrinsic::Min,
+                    args: vec![as_u32(input), last],
+                    ty: IRType::UInt(32),
+                };
             }
         }
+        Expression::Binary { left, right, .. } | Expression::Init { left, right, .. } => {
+            clamp_reads(left, lengths);
+            clamp_reads(right, lengths);
+        }
+        Expression::Unary { input, .. }
+        | Expression::Cast { input, .. }
+        | Expression::Field { base: input, .. } => clamp_reads(input, lengths),
+        Expression::Intrinsic { args, .. }
+        | Expression::Call { args, .. }
+        | Expression::Tuple { elements: args, .. } => {
+            for arg in args {
+                clamp_reads(arg, lengths);
+            }
+        }
+        Expression::Block { block, .. } => check_bounds(block, lengths),
+        Expression::Branch {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            clamp_reads(condition, lengths);
+            check_bounds(then_block, lengths);
+            if let Some(else_block) = else_block {
+                check_bounds(else_block, lengths);
+            }
+        }
+        Expression::Switch {
+            value,
+            cases,
+            default,
+        } => {
+            clamp_reads(value, lengths);
+            for (_, block) in cases {
+                check_bounds(block, lengths);
+            }
+            check_bounds(default, lengths);
+        }
+        Expression::Variable { .. }
+        | Expression::Literal { .. }
+        | Expression::SharedMemory { .. } => {}
+    }
+}
+
+fn clamp_target(target: &mut Expression, lengths: &[(String, Length)]) {
+    match target {
+        Expression::Index { index, .. } => clamp_reads(index, lengths),
+        Expression::Field { base, .. } => clamp_target(base, lengths),
+        target => clamp_reads(target, lengths),
+    }
+}
+
+/// Structs that contain bools, which are bound to uniforms through a copy with `u32`s. Structs
+/// are collected after the structs used by their fields.
+fn collect_host_structs(ty: IRType, structs: &mut Vec<&'static StructType>) {
+    if let IRType::Struct(ty) = ty {
+        if needs_host_type(IRType::Struct(ty)) && structs.iter().all(|other| other.name != ty.name)
+        {
+            for (_, field) in ty.fields.iter() {
+                collect_host_structs(*field, structs);
+            }
+            structs.push(ty);
+        }
+    }
+}
+
+fn needs_host_type(ty: IRType) -> bool {
+    match ty {
+        IRType::Bool => true,
+        IRType::Struct(ty) => ty.fields.iter().any(|(_, field)| needs_host_type(*field)),
+        _ => false,
+    }
+}
+
+/// Convert the value of a uniform from its host type
+fn from_host(value: String, ty: IRType) -> String {
+    match ty {
+        IRType::Bool => format!("{value} != 0u"),
+        IRType::Struct(struct_ty) if needs_host_type(ty) => {
+            let fields = struct_ty
+                .fields
+                .iter()
+                .map(|(name, field)| from_host(format!("{value}.{name}"), *field))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{}({fields})", struct_ty.name)
+        }
+        _ => value,
+    }
+}
+
+/// Collect the helper functions used in a block. Each helper is only collected once.
+fn collect_helpers(block: &[Statement], helpers: &mut Vec<Helper>) {
+    visit_block(block, &mut |expression| {
+        let helper = match expression {
+            Expression::Cast { from, to, .. } => Helper::cast(*from, *to),
+            Expression::Intrinsic {
+                intrinsic: Intrinsic::Pow | Intrinsic::Powi,
+                ty,
+                ..
+            } => Some(Helper::Pow(*ty)),
+            _ => None,
+        };
+        if let Some(helper) = helper {
+            let name = helper.to_string();
+            if helpers.iter().all(|other| other.to_string() != name) {
+                helpers.push(helper);
+            }
+        }
+    });
+}
+
+/// Operation that's emitted as a function, so its operands are only evaluated once
+#[derive(Clone, Copy)]
+enum Helper {
+    /// Float to integer cast. WGSL value constructors clamp out of range floats like Rust's
+    /// saturating `as`, but the result for `NaN` is unspecified, so it's selected to `0`.
+    FloatToInt { from: IRType, to: IRType },
+    /// Power with an integer exponent, computed by squaring. Signed exponents are only used for
+    /// floats, where a negative exponent gives the reciprocal.
+    Pow(IRType),
+}
+
+impl Helper {
+    fn cast(from: IRType, to: IRType) -> Option<Helper> {
+        match (from, to) {
+            (IRType::Float(_), IRType::Int(_) | IRType::UInt(_)) => {
+                Some(Helper::FloatToInt { from, to })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Name of the helper function
+impl Display for Helper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Helper::FloatToInt { from, to } => {
+                write!(f, "_cast_{}_{}", WgpuType(*from), WgpuType(*to))
+            }
+            Helper::Pow(ty) => write!(f, "_pow_{}", WgpuType(*ty)),
+        }
+    }
+}
+
+struct HelperFunction(Helper);
+
+impl Display for HelperFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = self.0;
+        match self.0 {
+            Helper::FloatToInt { from, to } => {
+                let (from, to) = (WgpuType(from), WgpuType(to));
+                // WGSL implementations may assume floats aren't `NaN`, so `x != x` isn't reliable
+                // and the bits are checked instead
+                writeln!(f, "fn {name}(x: {from}) -> {to} {{")?;
+                writeln!(
+                    f,
+                    "let is_nan = (bitcast<u32>(f32(x)) & 0x7fffffffu) > 0x7f800000u;"
+                )?;
+                writeln!(f, "return select({to}(x), {to}(0), is_nan);")?;
+                writeln!(f, "}}")
+            }
+            Helper::Pow(ty) => {
+                let is_float = matches!(ty, IRType::Float(_));
+                let ty = WgpuType(ty);
+                let exp = if is_float { "i32" } else { "u32" };
+                writeln!(f, "fn {name}(x: {ty}, exp: {exp}) -> {ty} {{")?;
+                writeln!(f, "var base = x;")?;
+                // `abs(i32::MIN)` wraps to itself, which is still the right magnitude as a `u32`
+                match is_float {
+                    true => writeln!(f, "var n = u32(abs(exp));")?,
+                    false => writeln!(f, "var n = exp;")?,
+                }
+                writeln!(f, "var result = {ty}(1);")?;
+                writeln!(f, "loop {{")?;
+                writeln!(f, "if n == 0u {{ break; }}")?;
+                writeln!(f, "if (n & 1u) == 1u {{ result *= base; }}")?;
+                writeln!(f, "base *= base;")?;
+                writeln!(f, "n >>= 1u;")?;
+                writeln!(f, "}}")?;
+                match is_float {
+                    true => writeln!(f, "return select(result, 1.0 / result, exp < 0i);")?,
+                    false => writeln!(f, "return result;")?,
+                }
+                writeln!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_branch(
+    f: &mut Formatter<'_>,
+    condition: &Expression,
+    then_block: &[Statement],
+    else_block: Option<&[Statement]>,
+    scope: JumpScope<'_>,
+) -> std::fmt::Result {
+    let condition = e(condition);
+    let then_block = WgpuBlock(then_block, scope);
+    write!(f, "if {condition} {{\n{then_block}}}")?;
+
+    let Some(else_block) = else_block else {
+        return Ok(());
+    };
+    write!(f, " else ")?;
+    if let [Statement::Expression { expression }] = else_block {
+        if let Expression::Branch {
+            condition,
+            then_block,
+            else_block,
+        } = &**expression
+        {
+            // Print `else if` chains flat instead of as nested blocks. Conditions with blocks need
+            // the nested block to hoist them into.
+            if !contains_block(condition) {
+                return write_branch(f, condition, then_block, else_block.as_deref(), scope);
+            }
+        }
+    }
+    let else_block = WgpuBlock(else_block, scope);
+    write!(f, "{{\n{else_block}}}")
+}
+
+fn write_switch(
+    f: &mut Formatter<'_>,
+    value: &Expression,
+    cases: &[(Vec<Expression>, Vec<Statement>)],
+    default: &[Statement],
+    scope: JumpScope<'_>,
+) -> std::fmt::Result {
+    let inner = JumpScope {
+        switch_depth: scope.switch_depth + 1,
+        ..scope
+    };
+
+    writeln!(f, "switch {} {{", e(value))?;
+    for (values, block) in cases {
+        let values = values
+            .iter()
+            .map(|value| e(value).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(f, "case {values}: {{")?;
+        write!(f, "{}", WgpuBlock(block, inner))?;
+        writeln!(f, "}}")?;
+    }
+    writeln!(f, "default: {{")?;
+    write!(f, "{}", WgpuBlock(default, inner))?;
+    writeln!(f, "}}")?;
+    writeln!(f, "}}")?;
+
+    let mut jumps = Vec::new();
+    let mut loops = scope.loops.to_vec();
+    for block in cases
+        .iter()
+        .map(|(_, block)| block.as_slice())
+        .chain([default])
+    {
+        collect_jumps(block, &mut loops, inner.switch_depth, &mut jumps);
+    }
+    // `break` only leaves the `switch`, so every jump to a loop has to be propagated
+    jumps.retain(|jump| jump.depth < scope.loops.len());
+    scope.write_jump_checks(f, &jumps)
+}
+
+fn write_loop(
+    f: &mut Formatter<'_>,
+    label: Option<&str>,
+    header: &str,
+    condition: Option<&Expression>,
+    block: &[Statement],
+    scope: JumpScope<'_>,
+) -> std::fmt::Result {
+    let depth = scope.loops.len();
+    let mut loops = scope.loops.to_vec();
+    loops.push(label);
+    let inner = JumpScope {
+        loops: &loops,
+        switch_depth: 0,
+        ..scope
+    };
+
+    let mut jumps = Vec::new();
+    collect_jumps(block, &mut loops.clone(), 0, &mut jumps);
+    let flag = |kind| {
+        let jump = Jump { kind, depth };
+        jumps.contains(&jump).then_some(jump)
+    };
+    let break_flag = flag(JumpKind::Break);
+    let continue_flag = flag(JumpKind::Continue);
+
+    if let Some(flag) = break_flag {
+        // Scope the flag so sibling loops don't collide
+        writeln!(f, "{{")?;
+        writeln!(f, "var {flag} = false;")?;
+    }
+    writeln!(f, "{header}")?;
+    // The condition is checked at the start of each iteration, so its blocks are hoisted there
+    if let Some(condition) = condition {
+        let mut hoisted = Vec::new();
+        let condition = hoist_blocks(condition, &mut hoisted, inner);
+        write_hoisted(f, &hoisted, inner, |f| {
+            writeln!(f, "if !({}) {{ break; }}", e(&condition))
+        })?;
+    }
+    if let Some(flag) = continue_flag {
+        writeln!(f, "var {flag} = false;")?;
+    }
+    write!(f, "{}", WgpuBlock(block, inner))?;
+    writeln!(f, "}}")?;
+    if break_flag.is_some() {
+        writeln!(f, "}}")?;
+    }
+
+    // Propagate jumps that target a loop further out
+    jumps.retain(|jump| jump.depth < depth);
+    scope.write_jump_checks(f, &jumps)
+}
+
+/// Loops enclosing a statement, innermost last, and the number of `switch`es between the
+/// statement and the innermost loop. WGSL has no labelled jumps, and `break` inside a `switch`
+/// only leaves the `switch`. Jumps that can't be expressed directly are lowered to a flag for the
+/// targeted loop, which is checked after each nested loop or `switch` the jump has to leave.
+///
+/// The scope also counts the temporaries of the function, so nested hoisted blocks can't shadow
+/// each other.
+#[derive(Clone, Copy)]
+struct JumpScope<'a> {
+    loops: &'a [Option<&'a str>],
+    switch_depth: usize,
+    temporaries: &'a Cell<usize>,
+}
+
+impl<'a> JumpScope<'a> {
+    fn new(temporaries: &'a Cell<usize>) -> Self {
+        Self {
+            loops: &[],
+            switch_depth: 0,
+            temporaries,
+        }
+    }
+
+    /// Unique name for a hoisted temporary
+    fn temporary(&self) -> String {
+        self.unique("block")
+    }
+
+    /// Unique name for a hidden variable of the generated code
+    fn unique(&self, name: &str) -> String {
+        let index = self.temporaries.get();
+        self.temporaries.set(index + 1);
+        format!("_{name}_{index}")
+    }
+
+    fn jump(&self, kind: JumpKind, label: Option<&str>) -> Jump {
+        let depth = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|loop_label| *loop_label == Some(label))
+                .expect("Labels are validated by the macro"),
+            None => self.loops.len() - 1,
+        };
+        Jump { kind, depth }
+    }
+
+    /// Whether a jump can be expressed as a plain `break` or `continue`
+    fn is_direct(&self, jump: Jump) -> bool {
+        let innermost = jump.depth + 1 == self.loops.len();
+        match jump.kind {
+            JumpKind::Break => innermost && self.switch_depth == 0,
+            JumpKind::Continue => innermost,
+        }
+    }
+
+    fn write_jump(&self, f: &mut Formatter<'_>, jump: Jump) -> std::fmt::Result {
+        if self.is_direct(jump) {
+            writeln!(f, "{};", jump.kind)
+        } else {
+            writeln!(f, "{jump} = true;")?;
+            writeln!(f, "break;")
+        }
+    }
+
+    /// Continue jumps whose flag was set inside a nested construct
+    fn write_jump_checks(&self, f: &mut Formatter<'_>, jumps: &[Jump]) -> std::fmt::Result {
+        for &jump in jumps {
+            let kind = match self.is_direct(jump) {
+                true => jump.kind,
+                false => JumpKind::Break,
+            };
+            writeln!(f, "if {jump} {{ {kind}; }}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JumpKind {
+    Break,
+    Continue,
+}
+
+impl Display for JumpKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpKind::Break => write!(f, "break"),
+            JumpKind::Continue => write!(f, "continue"),
+        }
+    }
+}
+
+/// A jump to the loop at `depth`, displayed as the name of its flag
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Jump {
+    kind: JumpKind,
+    depth: usize,
+}
+
+impl Display for Jump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "_{}_{}", self.kind, self.depth)
+    }
+}
+
+/// Collect the jumps in `block` that need a flag
+fn collect_jumps<'a>(
+    block: &'a [Statement],
+    loops: &mut Vec<Option<&'a str>>,
+    switch_depth: usize,
+    jumps: &mut Vec<Jump>,
+) {
+    for statement in block {
+        match statement {
+            Statement::Break { label } | Statement::Continue { label } => {
+                let kind = match statement {
+                    Statement::Break { .. } => JumpKind::Break,
+                    _ => JumpKind::Continue,
+                };
+                // Only used to classify the jump, so it doesn't need the function's temporaries
+                let temporaries = Cell::new(0);
+                let scope = JumpScope {
+                    loops,
+                    switch_depth,
+                    temporaries: &temporaries,
+                };
+                let jump = scope.jump(kind, label.as_deref());
+                if !scope.is_direct(jump) && !jumps.contains(&jump) {
+                    jumps.push(jump);
+                }
+            }
+            Statement::ForLoop { label, block, .. }
+            | Statement::WhileLoop { label, block, .. }
+            | Statement::Loop { label, block } => {
+                loops.push(label.as_deref());
+                collect_jumps(block, loops, 0, jumps);
+                loops.pop();
+            }
+            Statement::Local { variable, .. } => {
+                if let Expression::Init { right, .. } = &**variable {
+                    collect_value_jumps(right, loops, switch_depth, jumps);
+                }
+            }
+            Statement::ImplicitReturn { expression }
+            | Statement::Return {
+                value: Some(expression),
+            } => {
+                collect_value_jumps(expression, loops, switch_depth, jumps);
+            }
+            Statement::Expression { expression } => match &**expression {
+                Expression::Block { .. } => {
+                    collect_value_jumps(expression, loops, switch_depth, jumps);
+                }
+                Expression::Assigment { right, .. } => {
+                    collect_value_jumps(right, loops, switch_depth, jumps);
+                }
+                Expression::Branch {
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    collect_jumps(then_block, loops, switch_depth, jumps);
+                    if let Some(else_block) = else_block {
+                        collect_jumps(else_block, loops, switch_depth, jumps);
+                    }
+                }
+                Expression::Switch { cases, default, .. } => {
+                    for (_, block) in cases {
+                        collect_jumps(block, loops, switch_depth + 1, jumps);
+                    }
+                    collect_jumps(default, loops, switch_depth + 1, jumps);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+fn collect_value_jumps<'a>(
+    value: &'a Expression,
+    loops: &mut Vec<Option<&'a str>>,
+    switch_depth: usize,
+    jumps: &mut Vec<Jump>,
+) {
+    if let Expression::Block { block, .. } = value {
+        collect_jumps(block, loops, switch_depth, jumps);
     }
 }
 
 impl<'a> Display for WgpuExpression<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match &**self.0 {
+        match self.0 {
             Expression::Binary {
                 left,
                 operator,
                 right,
                 ..
             } => {
-                let left = e(left);
-                let operator = o(operator);
-                let right = e(right);
+                let right = right_operand(operator, right);
+                let operator = o(operator, left.ir_type());
+                let left = p(left);
                 write!(f, "{left} {operator} {right}",)
             }
             Expression::Unary {
                 input, operator, ..
             } => {
-                let input = e(input);
-                let operator = o(operator);
+                let operator = o(operator, input.ir_type());
+                let input = p(input);
                 write!(f, "{operator}{input}")
             }
             Expression::Variable { name, .. } => write!(f, "{name}"),
@@ -101,9 +1636,65 @@ impl<'a> Display for WgpuExpression<'a> {
                 let right = e(right);
                 write!(f, "{left} = {right}")
             }
+            Expression::CompoundAssignment {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let right = right_operand(operator, right);
+                let operator = o(operator, left.ir_type());
+                let left = e(left);
+                write!(f, "{left} {operator}= {right}")
+            }
+            Expression::Cast { input, from, to } => match Helper::cast(*from, *to) {
+                Some(helper) => write!(f, "{helper}({})", e(input)),
+                None => write!(f, "{}({})", WgpuType(*to), e(input)),
+            },
+            Expression::Intrinsic {
+                intrinsic, args, ..
+            } => write_intrinsic(f, *intrinsic, args),
             Expression::Init { .. } => {
                 panic!("Init should be handled by `Statement::Local`");
             }
+            Expression::SharedMemory { .. } => {
+                panic!("Shared memory must be assigned to a local");
+            }
+            Expression::Index { array, index, .. } => {
+                let array = p(array);
+                let index = e(index);
+                write!(f, "{array}[{index}]")
+            }
+            // Tuples are declared as structs, so they're built with the struct constructor
+            Expression::Tuple { elements, ty } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| e(element).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{}({elements})", WgpuType(*ty))
+            }
+            Expression::Field { base, name, .. } => {
+                let base = p(base);
+                write!(f, "{base}.{name}")
+            }
+            Expression::Call { function, args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| e(arg).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{}({args})", function.name)
+            }
+            Expression::Block { .. } => {
+                panic!("Blocks should be hoisted by the enclosing statement");
+            }
+            Expression::Branch { .. } => {
+                panic!("Branch should be handled by `Statement::Expression`");
+            }
+            Expression::Switch { .. } => {
+                panic!("Switch should be handled by `Statement::Expression`");
+            }
         }
     }
 }
@@ -115,19 +1706,73 @@ impl<'a> Display for WgpuOperator<'a> {
             Operator::Sub => write!(f, "-"),
             Operator::Mul => write!(f, "*"),
             Operator::Div => write!(f, "/"),
+            // WGSL's `%` truncates like Rust's for both integers and floats, so the result has the
+            // sign of the left operand. Floats are computed as `a - b * trunc(a / b)`, which can
+            // lose precision for large quotients where Rust computes the exact remainder.
+            // Division by zero and `MIN % -1` panic in Rust but produce `0` for integers in WGSL.
+            Operator::Rem => write!(f, "%"),
+            Operator::BitAnd => write!(f, "&"),
+            Operator::BitOr => write!(f, "|"),
+            Operator::BitXor => write!(f, "^"),
+            Operator::Shl => write!(f, "<<"),
+            Operator::Shr => write!(f, ">>"),
+            Operator::Eq => write!(f, "=="),
+            Operator::Ne => write!(f, "!="),
+            Operator::Lt => write!(f, "<"),
+            Operator::Le => write!(f, "<="),
+            Operator::Gt => write!(f, ">"),
+            Operator::Ge => write!(f, ">="),
+            // WGSL's logical operators short-circuit like Rust's
+            Operator::And => write!(f, "&&"),
+            Operator::Or => write!(f, "||"),
             Operator::Deref => write!(f, "*"),
-            Operator::Not => write!(f, "!"),
+            Operator::Not => match self.1 {
+                IRType::Int(_) | IRType::UInt(_) => write!(f, "~"),
+                _ => write!(f, "!"),
+            },
             Operator::Neg => write!(f, "-"),
         }
     }
 }
 
+/// Type of a value in a uniform, with bools stored as `u32`
+struct HostType(IRType);
+
+impl Display for HostType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            IRType::Bool => write!(f, "u32"),
+            IRType::Struct(ty) if needs_host_type(self.0) => write!(f, "{}_host", ty.name),
+            ty => write!(f, "{}", WgpuType(ty)),
+        }
+    }
+}
+
+/// Attributes of a struct member. Members that are structs are aligned and padded to 16 bytes
+/// like `StructType::layout`, since the uniform address space requires it.
+struct MemberLayout(IRType);
+
+impl Display for MemberLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            IRType::Struct(_) => {
+                let size = self.0.size().next_multiple_of(16);
+                write!(f, "@align(16) @size({size}) ")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Display for WgpuType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let ty = match self.0 {
             IRType::Int(32) => "i32",
             IRType::UInt(32) => "u32",
-            IRType::Float(16) => "f32",
+            IRType::Float(16) => "f16",
+            IRType::Float(32) => "f32",
+            IRType::Bool => "bool",
+            IRType::Struct(ty) => ty.name,
             t => panic!("Unsupported data type {:?}", t),
         };
         write!(f, "{ty}")
@@ -139,6 +1784,7 @@ fn format_lit(f: &mut Formatter<'_>, value: &str, ty: &IRType) -> Result<(), Err
         IRType::Int(32) => "i",
         IRType::UInt(32) => "u",
         IRType::Float(32) => "f",
+        IRType::Bool => "",
         t => panic!("Unsupported data type {:?}", t),
     };
     write!(f, "{value}{suffix}")
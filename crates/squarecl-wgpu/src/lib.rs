@@ -5,10 +5,17 @@ pub mod codegen;
 const ALPHA: i32 = 10;
 
 #[square]
+// Kernels only run through their expansion, so the Rust body is never executed
+#[allow(unused_variables, unused_assignments)]
 pub fn test_kernel(a: i32, b: i32) {
     let mut d = 0;
     let a = a * b;
     let c = a + b + ALPHA;
     d = a / c + 2;
+    for i in 0..b {
+        if i < c {
+            d = c * d + i;
+        }
+    }
     let f = 2u32;
 }
@@ -0,0 +1,156 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+pub fn if_else(a: u32, out: &mut Array<u32>) {
+    if a > 2 {
+        out[0] = 1;
+    } else if a > 1 {
+        out[0] = 2;
+    } else {
+        out[0] = 3;
+    }
+}
+
+#[test]
+fn if_else_chain() {
+    assert_wgsl(
+        if_else::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn if_else() {
+if a > 2u {
+if 0u < arrayLength(&out) {
+out[0i] = 1u;
+}
+} else if a > 1u {
+if 0u < arrayLength(&out) {
+out[0i] = 2u;
+}
+} else if 0u < arrayLength(&out) {
+out[0i] = 3u;
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn modes(a: i32, out: &mut Array<i32>) {
+    match a {
+        1 | 2 => out[0] = 10,
+        3 => {
+            let y = a * 2;
+            out[0] = y + 1;
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn match_arms() {
+    assert_wgsl(
+        modes::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<i32>;
+@group(0) @binding(1) var<uniform> a: i32;
+
+@compute @workgroup_size(1, 1, 1)
+fn modes() {
+switch a {
+case 1i, 2i: {
+if 0u < arrayLength(&out) {
+out[0i] = 10i;
+}
+}
+case 3i: {
+let y: i32 = a * 2i;
+if 0u < arrayLength(&out) {
+out[0i] = y + 1i;
+}
+}
+default: {
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn spin(n: u32) {
+    let mut i = 0;
+    while i < n {
+        i += 1;
+    }
+}
+
+#[square]
+pub fn tail_calls(a: u32, out: &mut Array<u32>) {
+    if a > 1 {
+        spin(a)
+    }
+    for i in 0..a {
+        spin(i)
+    }
+    match a {
+        3 => {
+            let b = a + 1;
+            spin(b)
+        }
+        4 => spin(1),
+        _ => {}
+    }
+    out[1] = 2;
+}
+
+#[test]
+fn unit_calls_in_tail_position() {
+    assert_wgsl(
+        tail_calls::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+fn spin(n: u32) {
+var i: u32 = 0u;
+loop {
+if !(i < n) { break; }
+i += 1u;
+}
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn tail_calls() {
+if a > 1u {
+spin(a);
+}
+let _end_1: u32 = a;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+spin(i);
+}
+switch a {
+case 3u: {
+let b: u32 = a + 1u;
+spin(b);
+}
+case 4u: {
+spin(1u);
+}
+default: {
+}
+}
+if 1u < arrayLength(&out) {
+out[1i] = 2u;
+}
+}
+"#,
+    );
+}
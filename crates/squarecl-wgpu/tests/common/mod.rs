@@ -0,0 +1,30 @@
+use naga::{
+    front::wgsl,
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+use squarecl_core::ir::Function;
+use squarecl_wgpu::codegen::WgpuKernel;
+
+/// Generate the WGSL for `kernel`, check that naga accepts it and compare it to `expected`.
+/// Leading and trailing whitespace is ignored.
+#[track_caller]
+pub fn assert_wgsl(kernel: Function, expected: &str) {
+    let shader = compile(kernel);
+    assert_eq!(shader.trim(), expected.trim(), "\n{shader}");
+}
+
+/// Generate the WGSL for `kernel` and check that naga accepts it
+#[track_caller]
+pub fn compile(kernel: Function) -> String {
+    let shader = WgpuKernel(kernel).to_string();
+    let module = match wgsl::parse_str(&shader) {
+        Ok(module) => module,
+        Err(error) => panic!("{}", error.emit_to_string(&shader)),
+    };
+    if let Err(error) =
+        Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module)
+    {
+        panic!("{}", error.emit_to_string(&shader));
+    }
+    shader
+}
@@ -0,0 +1,454 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::{Array, Float};
+use squarecl_macros::square;
+
+#[square]
+pub fn blocks(a: u32, out: &mut Array<u32>) {
+    let x = {
+        let a = a + 1;
+        a * 2
+    };
+    out[0] = x + a;
+}
+
+#[test]
+fn block_expressions() {
+    assert_wgsl(
+        blocks::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn blocks() {
+var x: u32;
+{
+let a: u32 = a + 1u;
+x = a * 2u;
+}
+if 0u < arrayLength(&out) {
+out[0i] = x + a;
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn intrinsics(a: f32, b: f32, out: &mut Array<f32>) {
+    let x = a.sqrt() + b.abs();
+    let y = f32::max(x, a.mul_add(b, 1.0));
+    out[0] = y.powf(2.0) + a.round();
+}
+
+#[test]
+fn math_intrinsics() {
+    assert_wgsl(
+        intrinsics::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: f32;
+@group(0) @binding(2) var<uniform> b: f32;
+
+@compute @workgroup_size(1, 1, 1)
+fn intrinsics() {
+let x: f32 = sqrt(a) + abs(b);
+let y: f32 = max(x, fma(a, b, 1f));
+if 0u < arrayLength(&out) {
+out[0i] = pow(y, 2f) + (trunc(a) + select(0.0, sign(a), abs(a - trunc(a)) >= 0.5));
+}
+}
+"#,
+    );
+}
+
+mod math {
+    use squarecl_macros::square;
+
+    #[square]
+    pub fn recip(a: f32) -> f32 {
+        a * 2.0
+    }
+}
+
+#[square]
+pub fn shadowed_intrinsic(a: f32, out: &mut Array<f32>) {
+    out[0] = math::recip(a) + f32::recip(a);
+}
+
+#[test]
+fn calls_named_like_intrinsics() {
+    assert_wgsl(
+        shadowed_intrinsic::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: f32;
+
+fn recip(a: f32) -> f32 {
+return a * 2f;
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn shadowed_intrinsic() {
+if 0u < arrayLength(&out) {
+out[0i] = recip(a) + (1.0 / a);
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn powers(a: u32, b: f32, out: &mut Array<f32>) {
+    let x = a.pow(3);
+    out[0] = b.powi(-2) + x as f32;
+}
+
+#[test]
+fn integer_exponents() {
+    assert_wgsl(
+        powers::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: u32;
+@group(0) @binding(2) var<uniform> b: f32;
+
+fn _pow_u32(x: u32, exp: u32) -> u32 {
+var base = x;
+var n = exp;
+var result = u32(1);
+loop {
+if n == 0u { break; }
+if (n & 1u) == 1u { result *= base; }
+base *= base;
+n >>= 1u;
+}
+return result;
+}
+
+fn _pow_f32(x: f32, exp: i32) -> f32 {
+var base = x;
+var n = u32(abs(exp));
+var result = f32(1);
+loop {
+if n == 0u { break; }
+if (n & 1u) == 1u { result *= base; }
+base *= base;
+n >>= 1u;
+}
+return select(result, 1.0 / result, exp < 0i);
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn powers() {
+let x: u32 = _pow_u32(a, 3u);
+if 0u < arrayLength(&out) {
+out[0i] = _pow_f32(b, -2i) + f32(x);
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn square(x: f32) -> f32 {
+    x * x
+}
+
+#[square(inline)]
+pub fn double(x: f32) -> f32 {
+    x * 2.0
+}
+
+#[square]
+pub fn clamped(x: f32) -> f32 {
+    if x < 0.0 {
+        return 0.0;
+    }
+    x
+}
+
+#[square]
+pub fn helpers(a: f32, out: &mut Array<f32>) {
+    out[0] = square(a) + double(a) + clamped(a);
+}
+
+#[test]
+fn helper_functions() {
+    assert_wgsl(
+        helpers::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: f32;
+
+fn square(x: f32) -> f32 {
+return x * x;
+}
+
+fn clamped(x: f32) -> f32 {
+if x < 0f {
+return 0f;
+}
+return x;
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn helpers() {
+if 0u < arrayLength(&out) {
+{
+var _block_0: f32;
+{
+let x: f32 = a;
+_block_0 = x * 2f;
+}
+out[0i] = (square(a) + _block_0) + clamped(a);
+}
+}
+}
+"#,
+    );
+}
+
+#[square(inline)]
+pub fn positive(x: f32) -> bool {
+    x > 0.0
+}
+
+#[square]
+pub fn inline_conditions(a: f32, n: u32, out: &mut Array<f32>) {
+    if a < 1.0 && positive(out[0]) {
+        out[0] = 1.0;
+    } else if a < -1.0 || positive(a) {
+        out[0] = 2.0;
+    }
+    let mut i = 0;
+    while positive(out[i]) {
+        i += 1;
+    }
+    for j in 0..square(a) as u32 {
+        out[j] = a;
+    }
+    for j in 0..({ n * 2 }) {
+        out[j] += a;
+    }
+}
+
+#[test]
+fn inlined_calls_in_conditions() {
+    assert_wgsl(
+        inline_conditions::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: f32;
+@group(0) @binding(2) var<uniform> n: u32;
+
+fn _cast_f32_u32(x: f32) -> u32 {
+let is_nan = (bitcast<u32>(f32(x)) & 0x7fffffffu) > 0x7f800000u;
+return select(u32(x), u32(0), is_nan);
+}
+
+fn square(x: f32) -> f32 {
+return x * x;
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn inline_conditions() {
+{
+var _block_0: bool;
+{
+_block_0 = false;
+if a < 1f {
+{
+let x: f32 = out[min(0u, arrayLength(&out) - 1u)];
+_block_0 = x > 0f;
+}
+}
+}
+if _block_0 {
+if 0u < arrayLength(&out) {
+out[0i] = 1f;
+}
+} else {
+{
+var _block_1: bool;
+{
+_block_1 = true;
+if !(a < -1f) {
+{
+let x: f32 = a;
+_block_1 = x > 0f;
+}
+}
+}
+if _block_1 {
+if 0u < arrayLength(&out) {
+out[0i] = 2f;
+}
+}
+}
+}
+}
+var i: i32 = 0i;
+loop {
+{
+var _block_2: bool;
+{
+let x: f32 = out[min(u32(i), arrayLength(&out) - 1u)];
+_block_2 = x > 0f;
+}
+if !(_block_2) { break; }
+}
+i += 1i;
+}
+let _end_4: u32 = _cast_f32_u32(square(a));
+for (var _i_3: u32 = 0u; _i_3 < _end_4; _i_3++) {
+var j: u32 = _i_3;
+if j < arrayLength(&out) {
+out[j] = a;
+}
+}
+{
+var _block_5: u32;
+{
+_block_5 = n * 2u;
+}
+let _end_7: u32 = _block_5;
+for (var _i_6: u32 = 0u; _i_6 < _end_7; _i_6++) {
+var j: u32 = _i_6;
+if j < arrayLength(&out) {
+out[j] += a;
+}
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn specialized(out: &mut Array<u32>, #[comptime] unroll: bool, #[comptime] size: u32) {
+    if unroll {
+        out[0] = size;
+    } else {
+        for i in 0..size {
+            out[i] = i;
+        }
+    }
+}
+
+#[test]
+fn comptime_parameters() {
+    assert_wgsl(
+        specialized::expand(true, 4),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+
+@compute @workgroup_size(1, 1, 1)
+fn specialized__true__4() {
+{
+if 0u < arrayLength(&out) {
+out[0i] = 4u;
+}
+}
+}
+"#,
+    );
+    assert_wgsl(
+        specialized::expand(false, 4),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+
+@compute @workgroup_size(1, 1, 1)
+fn specialized__false__4() {
+{
+for (var _i_0: u32 = 0u; _i_0 < 4u; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] = i;
+}
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn scaled(out: &mut Array<f32>, #[comptime] factor: f32) {
+    out[0] = factor;
+}
+
+#[test]
+fn comptime_names() {
+    assert_eq!(scaled::expand(1.5).name, "scaled__1_2e_5");
+    assert_eq!(scaled::expand(-15.0).name, "scaled___2d_15");
+    assert_eq!(
+        specialized::expand(false, 16).name,
+        "specialized__false__16"
+    );
+}
+
+#[square]
+pub fn times(x: u32, #[comptime] factor: u32) -> u32 {
+    x * factor
+}
+
+#[square]
+pub fn comptime_call(a: u32, out: &mut Array<u32>) {
+    out[0] = times(a, 3);
+}
+
+#[test]
+fn comptime_arguments() {
+    assert_wgsl(
+        comptime_call::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+fn times__3(x: u32) -> u32 {
+return x * 3u;
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn comptime_call() {
+if 0u < arrayLength(&out) {
+out[0i] = times__3(a);
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn axpy<F: Float>(a: F, x: &Array<F>, y: &mut Array<F>, n: u32) {
+    for i in 0..n {
+        y[i] = a * x[i] + y[i];
+    }
+}
+
+#[test]
+fn generic_kernel() {
+    assert_wgsl(
+        axpy::expand::<f32>(),
+        r#"
+@group(0) @binding(0) var<storage, read> x: array<f32>;
+@group(0) @binding(1) var<storage, read_write> y: array<f32>;
+@group(0) @binding(2) var<uniform> a: f32;
+@group(0) @binding(3) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn axpy__f32() {
+let _end_1: u32 = n;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&y) {
+y[i] = (a * x[min(i, arrayLength(&x) - 1u)]) + y[min(i, arrayLength(&y) - 1u)];
+}
+}
+}
+"#,
+    );
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+pub fn untyped_locals(a: u32, b: f32, out: &mut Array<f32>) {
+    let mut x = 0;
+    if a > 1 {
+        x = a;
+    }
+    let mut y = 0.0;
+    for i in 0..x {
+        y += b * i as f32;
+    }
+    out[x] = y;
+}
+
+#[test]
+fn infer_locals() {
+    assert_wgsl(
+        untyped_locals::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: u32;
+@group(0) @binding(2) var<uniform> b: f32;
+
+@compute @workgroup_size(1, 1, 1)
+fn untyped_locals() {
+var x: u32 = 0u;
+if a > 1u {
+x = a;
+}
+var y: f32 = 0f;
+let _end_1: u32 = x;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+y += (b * f32(i));
+}
+if x < arrayLength(&out) {
+out[x] = y;
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn literals(a: i32, out: &mut Array<f32>) {
+    let x = 2 * a + 1;
+    let y = 1.5;
+    out[0] = y * 2.0 + x as f32;
+}
+
+#[test]
+fn infer_literals() {
+    assert_wgsl(
+        literals::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: i32;
+
+@compute @workgroup_size(1, 1, 1)
+fn literals() {
+let x: i32 = (2i * a) + 1i;
+let y: f32 = 1.5f;
+if 0u < arrayLength(&out) {
+out[0i] = (y * 2f) + f32(x);
+}
+}
+"#,
+    );
+}
+
+pub type Index = u32;
+
+#[square]
+pub fn aliases(a: Index, b: core::primitive::u32, out: &mut Array<u32>) {
+    let mut x = a;
+    x += b;
+    out[0] = x;
+}
+
+#[test]
+fn type_aliases() {
+    assert_wgsl(
+        aliases::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+@group(0) @binding(2) var<uniform> b: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn aliases() {
+var x: u32 = a;
+x += b;
+if 0u < arrayLength(&out) {
+out[0i] = x;
+}
+}
+"#,
+    );
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use std::cell::RefCell;
+
+use common::{assert_wgsl, compile};
+use squarecl_core::{
+    ir::{Array, Float, Function},
+    runtime::{ArrayArg, ComputeClient, ExecutionMode, KernelArg, ScalarArg, WorkgroupCount},
+};
+use squarecl_macros::{square, SquareType};
+
+/// Records the launches instead of dispatching them
+#[derive(Default)]
+struct MockClient {
+    launches: RefCell<Vec<Launch>>,
+}
+
+struct Launch {
+    kernel: Function,
+    count: WorkgroupCount,
+    args: Vec<String>,
+    mode: ExecutionMode,
+}
+
+impl ComputeClient for MockClient {
+    type Handle = Vec<u8>;
+
+    fn execute(
+        &self,
+        kernel: Function,
+        count: WorkgroupCount,
+        args: Vec<KernelArg<'_, Self>>,
+        mode: ExecutionMode,
+    ) {
+        let args = args
+            .into_iter()
+            .map(|arg| match arg {
+                KernelArg::Array { len, ty, .. } => format!("array {ty} {len}"),
+                KernelArg::Scalar { bytes, ty } => format!("scalar {ty} {bytes:?}"),
+            })
+            .collect();
+        self.launches.borrow_mut().push(Launch {
+            kernel,
+            count,
+            args,
+            mode,
+        });
+    }
+}
+
+#[square(launch, launch_unchecked)]
+pub fn scale<F: Float>(input: &Array<F>, out: &mut Array<F>, factor: F, #[comptime] n: u32) {
+    for i in 0..n {
+        out[i] = input[i] * factor;
+    }
+}
+
+#[test]
+fn launch() {
+    let client = MockClient::default();
+    let input = vec![0; 16];
+    let out = vec![0; 16];
+    let count = WorkgroupCount::new(4, 1, 1);
+    scale::launch::<f32, _>(
+        &client,
+        count,
+        ArrayArg::new(&input, 4),
+        ArrayArg::new(&out, 4),
+        ScalarArg::new(2.5),
+        4,
+    );
+    unsafe {
+        scale::launch_unchecked::<f32, _>(
+            &client,
+            count,
+            ArrayArg::new(&input, 4),
+            ArrayArg::new(&out, 4),
+            ScalarArg::new(2.5),
+            4,
+        );
+    }
+
+    let launches = client.launches.into_inner();
+    let [checked, unchecked] = launches.as_slice() else {
+        panic!("Expected two launches");
+    };
+    assert_eq!(checked.mode, ExecutionMode::Checked);
+    assert_eq!(unchecked.mode, ExecutionMode::Unchecked);
+    for launch in [checked, unchecked] {
+        assert_eq!(launch.count, count);
+        assert_eq!(
+            launch.args,
+            ["array f32 4", "array f32 4", "scalar f32 [0, 0, 32, 64]"]
+        );
+    }
+    assert_wgsl(
+        checked.kernel.clone(),
+        r#"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out: array<f32>;
+@group(0) @binding(2) var<uniform> factor: f32;
+
+@compute @workgroup_size(1, 1, 1)
+fn scale__f32__4() {
+for (var _i_0: u32 = 0u; _i_0 < 4u; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] = input[min(i, arrayLength(&input) - 1u)] * factor;
+}
+}
+}
+"#,
+    );
+    assert_wgsl(
+        unchecked.kernel.clone(),
+        r#"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out: array<f32>;
+@group(0) @binding(2) var<uniform> factor: f32;
+
+@compute @workgroup_size(1, 1, 1)
+fn scale__f32__4() {
+for (var _i_0: u32 = 0u; _i_0 < 4u; _i_0++) {
+var i: u32 = _i_0;
+out[i] = input[i] * factor;
+}
+}
+"#,
+    );
+}
+
+/// Parameters can have the same names as the arguments of the generated launch function
+#[square(launch)]
+pub fn tally(kernel: &Array<u32>, client: &mut Array<u32>, count: u32) {
+    client[0] = kernel[0] + count;
+}
+
+#[test]
+fn launch_parameter_names() {
+    let client = MockClient::default();
+    let handle = vec![0; 16];
+    tally::launch(
+        &client,
+        WorkgroupCount::new(1, 1, 1),
+        ArrayArg::new(&handle, 4),
+        ArrayArg::new(&handle, 4),
+        ScalarArg::new(3),
+    );
+
+    let launches = client.launches.into_inner();
+    assert_eq!(launches[0].kernel.name, "tally");
+    assert_eq!(
+        launches[0].args,
+        ["array u32 4", "array u32 4", "scalar u32 [3, 0, 0, 0]"]
+    );
+}
+
+#[derive(SquareType)]
+pub struct Range {
+    start: u32,
+    end: u32,
+}
+
+#[derive(SquareType)]
+pub struct Window {
+    scale: f32,
+    range: Range,
+    enabled: bool,
+}
+
+#[square(launch)]
+pub fn windowed(out: &mut Array<f32>, window: Window) {
+    for i in window.range.start..window.range.end {
+        out[i] *= window.scale;
+    }
+}
+
+#[test]
+fn struct_arguments() {
+    let client = MockClient::default();
+    let out = vec![0; 16];
+    let window = Window {
+        scale: 2.5,
+        range: Range { start: 1, end: 3 },
+        enabled: true,
+    };
+    windowed::launch(
+        &client,
+        WorkgroupCount::new(1, 1, 1),
+        ArrayArg::new(&out, 4),
+        ScalarArg::new(window),
+    );
+
+    // Nested structs are aligned and padded to 16 bytes in uniforms
+    let mut expected = vec![0; 48];
+    expected[0..4].copy_from_slice(&2.5f32.to_le_bytes());
+    expected[16..20].copy_from_slice(&1u32.to_le_bytes());
+    expected[20..24].copy_from_slice(&3u32.to_le_bytes());
+    expected[32..36].copy_from_slice(&1u32.to_le_bytes());
+    let launches = client.launches.into_inner();
+    assert_eq!(launches[0].args[1], format!("scalar Window {expected:?}"));
+}
+
+const WIDTH: u32 = 64;
+
+#[square(workgroup_size = (WIDTH, block, 1), entry = "main")]
+pub fn configured(out: &mut Array<u32>, #[comptime] block: u32) {
+    out[0] = block;
+}
+
+#[test]
+fn entry_point() {
+    assert_wgsl(
+        configured::expand(4),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+
+@compute @workgroup_size(64, 4, 1)
+fn main() {
+if 0u < arrayLength(&out) {
+out[0i] = 4u;
+}
+}
+"#,
+    );
+    assert!(compile(configured::expand(2)).contains("@workgroup_size(64, 2, 1)"));
+}
+
+#[test]
+#[should_panic(expected = "Workgroup size dimensions must be nonzero, got [64, 0, 1]")]
+fn zero_workgroup_size() {
+    configured::expand(0);
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+pub fn ranges(n: u32, out: &mut Array<u32>) {
+    for i in 0..n {
+        out[i] = i;
+    }
+    for i in 2..=n {
+        out[i] += 1;
+    }
+}
+
+#[test]
+fn range_loops() {
+    assert_wgsl(
+        ranges::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn ranges() {
+let _end_1: u32 = n;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] = i;
+}
+}
+let _end_3: u32 = n;
+for (var _i_2: u32 = 2u; _i_2 <= _end_3; _i_2++) {
+var i: u32 = _i_2;
+if i < arrayLength(&out) {
+out[i] += 1u;
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn changing_bounds(n: u32, out: &mut Array<u32>) {
+    let mut end = n;
+    let step = 2;
+    for mut i in (0..end).step_by(step) {
+        end -= 1;
+        i *= 2;
+        out[i] = end;
+    }
+}
+
+#[test]
+fn range_evaluated_once() {
+    assert_wgsl(
+        changing_bounds::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn changing_bounds() {
+var end: u32 = n;
+let step: u32 = 2u;
+let _end_1: u32 = end;
+let _step_2: u32 = step;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0 += _step_2) {
+var i: u32 = _i_0;
+end -= 1u;
+i *= 2u;
+if i < arrayLength(&out) {
+out[i] = end;
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn while_loop(n: u32, out: &mut Array<u32>) {
+    let mut i = 0;
+    while i < n {
+        i += 1;
+        if i == 3 {
+            continue;
+        }
+        out[i] = i;
+    }
+    loop {
+        i -= 1;
+        if i == 0 {
+            break;
+        }
+    }
+}
+
+#[test]
+fn while_and_loop() {
+    assert_wgsl(
+        while_loop::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn while_loop() {
+var i: u32 = 0u;
+loop {
+if !(i < n) { break; }
+i += 1u;
+if i == 3u {
+continue;
+}
+if i < arrayLength(&out) {
+out[i] = i;
+}
+}
+loop {
+i -= 1u;
+if i == 0u {
+break;
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn labelled(n: u32, out: &mut Array<u32>) {
+    'outer: for i in 0..n {
+        for j in 0..n {
+            if j > i {
+                continue 'outer;
+            }
+            if i * j > 10 {
+                break 'outer;
+            }
+            match j {
+                4 => break,
+                5 => continue,
+                _ => {}
+            }
+            out[i] += j;
+        }
+    }
+}
+
+#[test]
+fn labelled_jumps() {
+    assert_wgsl(
+        labelled::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn labelled() {
+let _end_1: u32 = n;
+{
+var _break_0 = false;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var _continue_0 = false;
+var i: u32 = _i_0;
+let _end_3: u32 = n;
+{
+var _break_1 = false;
+for (var _i_2: u32 = 0u; _i_2 < _end_3; _i_2++) {
+var j: u32 = _i_2;
+if j > i {
+_continue_0 = true;
+break;
+}
+if (i * j) > 10u {
+_break_0 = true;
+break;
+}
+switch j {
+case 4u: {
+_break_1 = true;
+break;
+}
+case 5u: {
+continue;
+}
+default: {
+}
+}
+if _break_1 { break; }
+if i < arrayLength(&out) {
+out[i] += j;
+}
+}
+}
+if _continue_0 { continue; }
+if _break_0 { break; }
+}
+}
+}
+"#,
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::{Array, SharedMemory};
+use squarecl_macros::square;
+
+#[square]
+pub fn copy(input: &Array<f32>, out: &mut Array<f32>, n: u32) {
+    for i in 0..n {
+        out[i] = input[i] * 2.0;
+    }
+    out[0] += input[n - 1];
+}
+
+#[test]
+fn array_parameters() {
+    assert_wgsl(
+        copy::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out: array<f32>;
+@group(0) @binding(2) var<uniform> n: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn copy() {
+let _end_1: u32 = n;
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] = input[min(i, arrayLength(&input) - 1u)] * 2f;
+}
+}
+if 0u < arrayLength(&out) {
+out[0i] += input[min(n - 1u, arrayLength(&input) - 1u)];
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn reduce(input: &Array<f32>, out: &mut Array<f32>, #[comptime] size: u32) {
+    let mut tile = SharedMemory::<f32>::new(size);
+    for i in 0..size {
+        tile[i] = input[i];
+    }
+    out[0] = tile[0] + tile[1];
+}
+
+#[test]
+fn shared_memory() {
+    assert_wgsl(
+        reduce::expand(64),
+        r#"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out: array<f32>;
+
+var<workgroup> reduce__64_tile_0: array<f32, 64>;
+
+@compute @workgroup_size(1, 1, 1)
+fn reduce__64() {
+for (var _i_0: u32 = 0u; _i_0 < 64u; _i_0++) {
+var i: u32 = _i_0;
+if i < 64u {
+reduce__64_tile_0[i] = input[min(i, arrayLength(&input) - 1u)];
+}
+}
+if 0u < arrayLength(&out) {
+out[0i] = reduce__64_tile_0[0i] + reduce__64_tile_0[1i];
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn staged(x: f32) -> f32 {
+    let mut tile = SharedMemory::<f32>::new(4);
+    tile[0] = x;
+    tile[0]
+}
+
+#[square]
+pub fn shadowed(input: &Array<f32>, out: &mut Array<f32>) {
+    let mut tile = SharedMemory::<f32>::new(8);
+    tile[0] = staged(input[0]);
+    if input[1] > 0.0 {
+        let mut tile = SharedMemory::<u32>::new(2);
+        tile[0] = 1;
+        out[1] = tile[0] as f32;
+    }
+    out[0] = tile[0];
+}
+
+#[test]
+fn shared_memory_names() {
+    assert_wgsl(
+        shadowed::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out: array<f32>;
+
+var<workgroup> staged_tile_0: array<f32, 4>;
+var<workgroup> shadowed_tile_1: array<f32, 8>;
+var<workgroup> shadowed_tile_2: array<u32, 2>;
+
+fn staged(x: f32) -> f32 {
+staged_tile_0[0i] = x;
+return staged_tile_0[0i];
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn shadowed() {
+shadowed_tile_1[0i] = staged(input[min(0u, arrayLength(&input) - 1u)]);
+if input[min(1u, arrayLength(&input) - 1u)] > 0f {
+shadowed_tile_2[0i] = 1u;
+if 1u < arrayLength(&out) {
+out[1i] = f32(shadowed_tile_2[0i]);
+}
+}
+if 0u < arrayLength(&out) {
+out[0i] = shadowed_tile_1[0i];
+}
+}
+"#,
+    );
+}
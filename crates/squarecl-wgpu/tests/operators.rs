@@ -0,0 +1,199 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::Array;
+use squarecl_macros::square;
+
+#[square]
+pub fn comparisons(a: i32, b: i32, out: &mut Array<u32>) {
+    let c = a < b;
+    let d = a >= b && a != 0;
+    let e = c || !d;
+    if e == (a == b) {
+        out[0] = 1;
+    }
+}
+
+#[test]
+fn comparison_and_logical_operators() {
+    assert_wgsl(
+        comparisons::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: i32;
+@group(0) @binding(2) var<uniform> b: i32;
+
+@compute @workgroup_size(1, 1, 1)
+fn comparisons() {
+let c: bool = a < b;
+let d: bool = (a >= b) && (a != 0i);
+let e: bool = c || !d;
+if e == (a == b) {
+if 0u < arrayLength(&out) {
+out[0i] = 1u;
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn bitwise(a: u32, b: i32, out: &mut Array<u32>) {
+    let x = (a & 3) | (a ^ 5);
+    let y = x << 2 >> b;
+    out[0] = !y;
+}
+
+#[test]
+fn bitwise_operators() {
+    assert_wgsl(
+        bitwise::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+@group(0) @binding(2) var<uniform> b: i32;
+
+@compute @workgroup_size(1, 1, 1)
+fn bitwise() {
+let x: u32 = (a & 3u) | (a ^ 5u);
+let y: u32 = (x << u32(2i)) >> u32(b);
+if 0u < arrayLength(&out) {
+out[0i] = ~y;
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn remainder(a: i32, b: f32, out: &mut Array<f32>) {
+    let x = a % 3;
+    out[0] = b % 2.0 + x as f32;
+}
+
+#[test]
+fn remainder_operator() {
+    assert_wgsl(
+        remainder::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> a: i32;
+@group(0) @binding(2) var<uniform> b: f32;
+
+@compute @workgroup_size(1, 1, 1)
+fn remainder() {
+let x: i32 = a % 3i;
+if 0u < arrayLength(&out) {
+out[0i] = (b % 2f) + f32(x);
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn compound(a: u32, out: &mut Array<u32>) {
+    let mut x = a;
+    x += 1;
+    x -= 2;
+    x *= 3;
+    x /= 4;
+    x %= 5;
+    x &= 6;
+    x |= 7;
+    x ^= 8;
+    x <<= 1;
+    x >>= 2;
+    out[0] = x;
+}
+
+#[test]
+fn compound_assignment() {
+    assert_wgsl(
+        compound::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn compound() {
+var x: u32 = a;
+x += 1u;
+x -= 2u;
+x *= 3u;
+x /= 4u;
+x %= 5u;
+x &= 6u;
+x |= 7u;
+x ^= 8u;
+x <<= u32(1i);
+x >>= u32(2i);
+if 0u < arrayLength(&out) {
+out[0i] = x;
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn casts(a: f32, out: &mut Array<u32>) {
+    let x = a as i32;
+    out[0] = x as u32 + (a > 1.0) as u32;
+}
+
+#[test]
+fn cast() {
+    assert_wgsl(
+        casts::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: f32;
+
+fn _cast_f32_i32(x: f32) -> i32 {
+let is_nan = (bitcast<u32>(f32(x)) & 0x7fffffffu) > 0x7f800000u;
+return select(i32(x), i32(0), is_nan);
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn casts() {
+let x: i32 = _cast_f32_i32(a);
+if 0u < arrayLength(&out) {
+out[0i] = u32(x) + u32(a > 1f);
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn bool_literals(a: u32, out: &mut Array<u32>) {
+    let mut found = false;
+    if a > 2 {
+        found = true;
+    }
+    out[0] = found as u32;
+}
+
+#[test]
+fn boolean_literals() {
+    assert_wgsl(
+        bool_literals::expand(),
+        r#"
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn bool_literals() {
+var found: bool = false;
+if a > 2u {
+found = true;
+}
+if 0u < arrayLength(&out) {
+out[0i] = u32(found);
+}
+}
+"#,
+    );
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::assert_wgsl;
+use squarecl_core::ir::Array;
+use squarecl_macros::{square, SquareType};
+
+#[derive(SquareType, Clone, Copy)]
+pub struct Shape {
+    x: u32,
+    y: u32,
+}
+
+#[square]
+pub fn size(shape: Shape) -> u32 {
+    shape.x * shape.y
+}
+
+#[square]
+pub fn structs(out: &mut Array<f32>, shape: Shape, scale: f32) {
+    for i in 0..size(shape) {
+        out[i] *= scale;
+    }
+}
+
+#[test]
+fn struct_parameters() {
+    assert_wgsl(
+        structs::expand(),
+        r#"
+struct Shape {
+x: u32,
+y: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> shape: Shape;
+@group(0) @binding(2) var<uniform> scale: f32;
+
+fn size(shape: Shape) -> u32 {
+return shape.x * shape.y;
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn structs() {
+let _end_1: u32 = size(shape);
+for (var _i_0: u32 = 0u; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] *= scale;
+}
+}
+}
+"#,
+    );
+}
+
+#[derive(SquareType, Clone, Copy)]
+pub struct Range {
+    start: u32,
+    end: u32,
+}
+
+#[derive(SquareType, Clone, Copy)]
+pub struct Window {
+    range: Range,
+    scale: f32,
+    enabled: bool,
+}
+
+#[square]
+pub fn uniforms(out: &mut Array<f32>, window: Window, clear: bool) {
+    if window.enabled {
+        for i in window.range.start..window.range.end {
+            out[i] *= window.scale;
+        }
+    }
+    if clear {
+        out[0] = 0.0;
+    }
+}
+
+#[test]
+fn uniform_layout() {
+    assert_wgsl(
+        uniforms::expand(),
+        r#"
+struct Range {
+start: u32,
+end: u32,
+}
+
+struct Window {
+@align(16) @size(16) range: Range,
+scale: f32,
+enabled: bool,
+}
+
+struct Window_host {
+@align(16) @size(16) range: Range,
+scale: f32,
+enabled: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> out: array<f32>;
+@group(0) @binding(1) var<uniform> window: Window_host;
+@group(0) @binding(2) var<uniform> clear: u32;
+
+@compute @workgroup_size(1, 1, 1)
+fn uniforms() {
+let window: Window = Window(window.range, window.scale, window.enabled != 0u);
+let clear: bool = clear != 0u;
+if window.enabled {
+let _end_1: u32 = window.range.end;
+for (var _i_0: u32 = window.range.start; _i_0 < _end_1; _i_0++) {
+var i: u32 = _i_0;
+if i < arrayLength(&out) {
+out[i] *= window.scale;
+}
+}
+}
+if clear {
+if 0u < arrayLength(&out) {
+out[0i] = 0f;
+}
+}
+}
+"#,
+    );
+}
+
+#[square]
+pub fn min_max(a: u32, b: u32) -> (u32, u32) {
+    (u32::min(a, b), u32::max(a, b))
+}
+
+#[square]
+pub fn tuples(a: u32, b: u32, out: &mut Array<u32>) {
+    let (low, high) = min_max(a, b);
+    let pair = (low, 1.0f32);
+    out[0] = high - pair.0;
+}
+
+#[test]
+fn tuple_destructuring() {
+    assert_wgsl(
+        tuples::expand(),
+        r#"
+struct Tuple2_u32_u32 {
+_0: u32,
+_1: u32,
+}
+
+struct Tuple2_u32_f32 {
+_0: u32,
+_1: f32,
+}
+
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@group(0) @binding(1) var<uniform> a: u32;
+@group(0) @binding(2) var<uniform> b: u32;
+
+fn min_max(a: u32, b: u32) -> Tuple2_u32_u32 {
+return Tuple2_u32_u32(min(a, b), max(a, b));
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn tuples() {
+let _tuple_0 = min_max(a, b);
+let low = _tuple_0._0;
+let high: u32 = _tuple_0._1;
+let pair = Tuple2_u32_f32(low, 1f);
+if 0u < arrayLength(&out) {
+out[0i] = high - pair._0;
+}
+}
+"#,
+    );
+}